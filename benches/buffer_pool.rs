@@ -0,0 +1,36 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ua4f::utils::buffer_pool::BufferPool;
+
+/// 每个连接的两个转发缓冲区：未使用复用池时每次新分配并清零
+fn allocate(size: usize) -> (BytesMut, BytesMut) {
+    let mut a = BytesMut::with_capacity(size);
+    a.resize(size, 0);
+    let mut b = BytesMut::with_capacity(size);
+    b.resize(size, 0);
+    (a, b)
+}
+
+fn per_connection_buffers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("copy_buffers");
+    // 默认的 --copy-buffer-size 与较大的取值
+    for size in [5 * 1024, 64 * 1024] {
+        let pool = BufferPool::new(size, 64);
+        group.bench_with_input(BenchmarkId::new("pooled", size), &size, |b, _| {
+            b.iter(|| {
+                let (a, b) = (pool.get(), pool.get());
+                black_box((a[0], b[0]));
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("allocated", size), &size, |b, &size| {
+            b.iter(|| {
+                let (a, b) = allocate(size);
+                black_box((a[0], b[0]));
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, per_connection_buffers);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use std::sync::Arc;
+
+use ua4f::http::{self, RewriteConfig, RewriteOutcome};
+use ua4f::utils::rule_cache::HostRuleCache;
+
+const REQUEST: &[u8] = b"GET /index.html HTTP/1.1\r\n\
+Host: example.com\r\n\
+User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+Accept: text/html,application/xhtml+xml\r\n\
+Accept-Language: en-US,en;q=0.5\r\n\
+Connection: keep-alive\r\n\r\n";
+
+/// 与读取请求头时一样，缓冲区留有余量，变长的改写通常不需要重新分配
+fn request_buffer() -> BytesMut {
+    let mut buf = BytesMut::with_capacity(4096);
+    buf.extend_from_slice(REQUEST);
+    buf
+}
+
+fn rewrite(c: &mut Criterion) {
+    let config = RewriteConfig::default();
+    let old_len = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0".len();
+    let cases = [
+        ("shorter", "UA4F/1.0".to_string()),
+        ("equal", "U".repeat(old_len)),
+        ("longer", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.5.1.4 Safari/537.36 Edg/114.5.1.4".to_string()),
+    ];
+
+    let mut group = c.benchmark_group("modify_user_agent");
+    for (name, user_agent) in &cases {
+        let mut buf = request_buffer();
+        assert_eq!(http::modify_user_agent(&mut buf, user_agent, None, &config), RewriteOutcome::Rewritten);
+
+        group.bench_function(*name, |b| {
+            b.iter_batched(
+                request_buffer,
+                |mut buf| {
+                    http::modify_user_agent(&mut buf, black_box(user_agent), None, &config);
+                    buf
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// 100 条 --host-user-agents 规则，目标主机只匹配最后一条：缓存命中时跳过逐条 glob 匹配
+fn host_rules(c: &mut Criterion) {
+    let rules: Vec<_> = (0..100)
+        .map(|i| (format!("*.site{i}.example"), Arc::new(format!("Site{i}/1.0"))))
+        .collect();
+    let cases = [("uncached", HostRuleCache::new(0)), ("cached", HostRuleCache::default())];
+
+    let mut group = c.benchmark_group("host_user_agent");
+    for (name, cache) in cases {
+        let config = RewriteConfig { host_user_agents: rules.clone(), host_rule_cache: cache, ..RewriteConfig::default() };
+        assert_eq!(config.host_user_agent("cdn.site99.example").map(|ua| ua.as_str()), Some("Site99/1.0"));
+        group.bench_function(name, |b| b.iter(|| config.host_user_agent(black_box("cdn.site99.example")).is_some()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rewrite, host_rules);
+criterion_main!(benches);
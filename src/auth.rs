@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use base64::Engine;
+use blake2::digest::{KeyInit, Mac};
+use blake2::Blake2bMac512;
+use moka::future::Cache;
+use socks5_server::proto::handshake::password::{Error as PasswordError, Request as PasswordRequest, Response as PasswordResponse};
+use socks5_server::proto::handshake::Method;
+use socks5_server::Auth;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::warn;
+use zeroize::Zeroize;
+
+/// 认证成功的缓存时间，避免同一客户端频繁连接时反复请求后端
+const AUTH_CACHE_TTL: Duration = Duration::from_secs(60);
+/// HTTP 回调认证的超时时间
+const HTTP_CALLBACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SOCKS5 用户名/密码的校验后端
+#[async_trait]
+pub trait AuthBackend: Send + Sync {
+    async fn authenticate(&self, user: &[u8], pass: &[u8]) -> bool;
+}
+
+/// 凭据文件：每行 `user:hash`，hash 为 bcrypt（`$2b$...`）或 argon2（`$argon2id$...`）格式；
+/// 空行与 `#` 开头的注释行被忽略
+pub struct FileBackend {
+    users: HashMap<Vec<u8>, Arc<str>>,
+}
+
+impl FileBackend {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut users = HashMap::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((user, hash)) = line.split_once(':') else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("第 {} 行格式应为 user:hash", number + 1),
+                ));
+            };
+            if !hash.starts_with("$2") && !hash.starts_with("$argon2") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("第 {} 行的密码哈希不是 bcrypt 或 argon2 格式", number + 1),
+                ));
+            }
+            users.insert(user.as_bytes().to_vec(), Arc::from(hash));
+        }
+        Ok(FileBackend { users })
+    }
+}
+
+fn verify_hash(pass: &[u8], hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        use argon2::{Argon2, PasswordHash, PasswordVerifier};
+        PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(pass, &hash).is_ok())
+    } else {
+        bcrypt::verify(pass, hash).unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl AuthBackend for FileBackend {
+    async fn authenticate(&self, user: &[u8], pass: &[u8]) -> bool {
+        let Some(hash) = self.users.get(user).cloned() else {
+            return false;
+        };
+        // 哈希校验刻意设计得很慢，放到阻塞线程池中执行，避免占用异步工作线程
+        let pass = pass.to_vec();
+        tokio::task::spawn_blocking(move || verify_hash(&pass, &hash))
+            .await
+            .unwrap_or(false)
+    }
+}
+
+/// HTTP 回调：以 `Authorization: Basic` 携带凭据向 `http://host:port/path` 发送 GET，2xx 表示认证成功
+pub struct HttpCallbackBackend {
+    host: String,
+    path: String,
+}
+
+impl HttpCallbackBackend {
+    pub fn new(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("仅支持 http:// 地址: {}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos..]),
+            None => (rest, "/"),
+        };
+        if authority.is_empty() {
+            return Err(format!("缺少主机名: {}", url));
+        }
+        let host = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+        Ok(HttpCallbackBackend { host, path: path.to_string() })
+    }
+
+    async fn request(&self, user: &[u8], pass: &[u8]) -> io::Result<bool> {
+        let mut credentials = user.to_vec();
+        credentials.push(b':');
+        credentials.extend_from_slice(pass);
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Basic {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        );
+
+        let mut stream = TcpStream::connect(self.host.as_str()).await?;
+        stream.write_all(request.as_bytes()).await?;
+        // 只需要状态行
+        let mut response = [0u8; 32];
+        let mut filled = 0;
+        while filled < 12 {
+            let n = stream.read(&mut response[filled..]).await?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        let status = response[..filled].split(|&c| c == b' ').nth(1);
+        Ok(response.starts_with(b"HTTP/1.") && status.is_some_and(|status| status.starts_with(b"2")))
+    }
+}
+
+#[async_trait]
+impl AuthBackend for HttpCallbackBackend {
+    async fn authenticate(&self, user: &[u8], pass: &[u8]) -> bool {
+        match tokio::time::timeout(HTTP_CALLBACK_TIMEOUT, self.request(user, pass)).await {
+            Ok(Ok(accepted)) => accepted,
+            Ok(Err(err)) => {
+                warn!("认证回调 {} 请求失败: {}", self.host, err);
+                false
+            }
+            Err(_) => {
+                warn!("认证回调 {} 超时", self.host);
+                false
+            }
+        }
+    }
+}
+
+/// SOCKS5 认证结果
+#[derive(Debug)]
+pub enum AuthOutcome {
+    /// 未启用认证
+    Anonymous,
+    /// 认证通过的用户名（非 UTF-8 字节按替换字符转换）
+    User(String),
+    Rejected,
+}
+
+/// SOCKS5 认证：未配置后端时不认证，否则要求用户名/密码并交给后端校验，成功结果短暂缓存
+pub struct Socks5Auth {
+    backend: Option<Box<dyn AuthBackend>>,
+    /// 以凭据的带密钥哈希为键，内存中不保留明文密码
+    cache: Cache<[u8; 64], ()>,
+    /// 进程启动时随机生成的哈希密钥
+    cache_key: [u8; 32],
+}
+
+impl Socks5Auth {
+    pub fn new(backend: Option<Box<dyn AuthBackend>>) -> Self {
+        let mut cache_key = [0; 32];
+        getrandom::fill(&mut cache_key).expect("无法从系统获取随机数");
+        Socks5Auth {
+            backend,
+            cache: Cache::builder()
+                .max_capacity(1024)
+                .time_to_live(AUTH_CACHE_TTL)
+                .build(),
+            cache_key,
+        }
+    }
+
+    /// 用户名带长度前缀，避免不同的用户名/密码拼接后得到相同的输入
+    fn credentials_digest(&self, user: &[u8], pass: &[u8]) -> [u8; 64] {
+        let mut mac = <Blake2bMac512 as KeyInit>::new_from_slice(&self.cache_key).expect("密钥长度有效");
+        mac.update(&(user.len() as u64).to_be_bytes());
+        mac.update(user);
+        mac.update(pass);
+        mac.finalize().into_bytes().into()
+    }
+
+    /// 先查缓存，未命中再交给后端校验
+    async fn check(&self, backend: &dyn AuthBackend, user: &[u8], pass: &[u8]) -> bool {
+        let digest = self.credentials_digest(user, pass);
+        if self.cache.contains_key(&digest) {
+            return true;
+        }
+        if backend.authenticate(user, pass).await {
+            self.cache.insert(digest, ()).await;
+            return true;
+        }
+        false
+    }
+}
+
+impl Drop for Socks5Auth {
+    fn drop(&mut self) {
+        self.cache_key.zeroize();
+    }
+}
+
+#[async_trait]
+impl Auth for Socks5Auth {
+    type Output = Result<AuthOutcome, PasswordError>;
+
+    fn as_handshake_method(&self) -> Method {
+        if self.backend.is_some() {
+            Method::PASSWORD
+        } else {
+            Method::NONE
+        }
+    }
+
+    async fn execute(&self, stream: &mut TcpStream) -> Self::Output {
+        let Some(backend) = &self.backend else {
+            return Ok(AuthOutcome::Anonymous);
+        };
+        let mut req = PasswordRequest::read_from(stream).await?;
+        let user = String::from_utf8_lossy(&req.username).into_owned();
+        let accepted = self.check(backend.as_ref(), &req.username, &req.password).await;
+        req.username.zeroize();
+        req.password.zeroize();
+        PasswordResponse::new(accepted).write_to(stream).await?;
+        Ok(if accepted {
+            AuthOutcome::User(user)
+        } else {
+            AuthOutcome::Rejected
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 只接受 `user:pass`，记录被调用的次数
+    #[derive(Default)]
+    struct CountingBackend {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl AuthBackend for CountingBackend {
+        async fn authenticate(&self, user: &[u8], pass: &[u8]) -> bool {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            user == b"user" && pass == b"pass"
+        }
+    }
+
+    #[tokio::test]
+    async fn successful_logins_are_cached_by_digest() {
+        let backend = CountingBackend::default();
+        let calls = backend.calls.clone();
+        let auth = Socks5Auth::new(None);
+
+        assert!(auth.check(&backend, b"user", b"pass").await);
+        assert!(auth.check(&backend, b"user", b"pass").await);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // 密码不同不能命中缓存
+        assert!(!auth.check(&backend, b"user", b"wrong").await);
+        assert!(!auth.check(&backend, b"user", b"wrong").await);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn digest_does_not_contain_the_password_and_is_unambiguous() {
+        let auth = Socks5Auth::new(None);
+        let digest = auth.credentials_digest(b"user", b"secretpassword");
+        assert!(!digest.windows(14).any(|window| window == b"secretpassword"));
+        assert_ne!(auth.credentials_digest(b"ab", b"c"), auth.credentials_digest(b"a", b"bc"));
+        // 不同进程（实例）的密钥不同
+        assert_ne!(digest, Socks5Auth::new(None).credentials_digest(b"user", b"secretpassword"));
+    }
+}
@@ -1,87 +1,1026 @@
+//! HTTP 请求识别与 User-Agent 改写，可嵌入其他代理或中间件使用。
+//!
+//! 先用 [`is_http_request`] 判断客户端发来的数据是否为 HTTP 请求，收齐完整请求头（以 `\r\n\r\n` 结尾）后
+//! 交给 [`modify_user_agent`] 原地改写，再将结果写往目标：
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use ua4f::http::{self, HttpMethods, RewriteConfig, RewriteOutcome};
+//!
+//! let methods = HttpMethods::default();
+//! let config = RewriteConfig::default();
+//! let mut head = BytesMut::from(&b"GET / HTTP/1.1\r\nHost: example.com\r\nUser-Agent: curl/8.0\r\n\r\n"[..]);
+//!
+//! assert!(http::is_http_request(&head, &methods));
+//! assert_eq!(http::modify_user_agent(&mut head, "FFF", None, &config), RewriteOutcome::Rewritten);
+//! assert_eq!(&head[..], b"GET / HTTP/1.1\r\nHost: example.com\r\nUser-Agent: FFF\r\n\r\n");
+//! ```
+//!
+//! [`RewriteConfig::whitelist`] 为 `None` 时使用内置的 [`BUILTIN_WHITELIST`]，也可以提供自己的白名单：
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use ua4f::http::{self, RewriteConfig, RewriteOutcome, WhitelistEntry, WhitelistMatch};
+//!
+//! let config = RewriteConfig {
+//!     whitelist: Some(vec![WhitelistEntry { pattern: Box::from(&b"MyApp/"[..]), mode: Some(WhitelistMatch::Prefix) }]),
+//!     ..RewriteConfig::default()
+//! };
+//! let mut head = BytesMut::from(&b"GET / HTTP/1.1\r\nUser-Agent: MyApp/2.1\r\n\r\n"[..]);
+//!
+//! assert!(http::check_is_in_whitelist(b"MyApp/2.1", &config));
+//! assert_eq!(http::modify_user_agent(&mut head, "FFF", None, &config), RewriteOutcome::Whitelisted);
+//! assert_eq!(&head[..], b"GET / HTTP/1.1\r\nUser-Agent: MyApp/2.1\r\n\r\n");
+//! ```
+
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
 use bytes::BytesMut;
-use tracing::{error, debug};
+use tracing::{error, debug, info};
 use memchr::{memmem};
+use crate::utils::glob::glob_match;
+use crate::utils::rule_cache::HostRuleCache;
+
+/// User-Agent 改写的可选行为
+#[derive(Debug, Default)]
+pub struct RewriteConfig {
+    /// 仅改写请求路径匹配其中任一 glob 的请求，为空时改写全部请求
+    pub rewrite_paths: Vec<String>,
+    /// 不改写值为空的 User-Agent（`User-Agent: \r\n`），视为客户端有意为之
+    pub skip_empty_ua: bool,
+    /// 删除整行 User-Agent 头而不是替换其值
+    pub remove_ua: bool,
+    /// 只以 info 级别记录将要进行的改写，不修改请求
+    pub dry_run: bool,
+    /// 只改写 Accept 头包含 `text/html` 的请求（浏览器页面导航），不改写 API/XHR 请求
+    pub rewrite_on_accept_html: bool,
+    /// 只改写包含其中任一子串（忽略大小写）的 User-Agent，为空时改写全部
+    pub rewrite_if_matches: Vec<String>,
+    /// 请求缺少 User-Agent 头时在请求行之后插入一个，不受 rewrite_if_matches 限制
+    pub insert_missing_ua: bool,
+    /// 不改写的 User-Agent 列表（忽略大小写），为 None 时使用内置列表
+    pub whitelist: Option<Vec<WhitelistEntry>>,
+    /// 未单独指定匹配方式的白名单条目（含内置列表）使用的匹配方式
+    pub whitelist_match: WhitelistMatch,
+    /// 按请求行的 HTTP 版本（如 `HTTP/1.0`）覆盖的 User-Agent，未匹配的版本使用全局值
+    pub version_user_agents: Vec<(String, String)>,
+    /// 按 SOCKS5 认证用户名覆盖的 User-Agent，优先于按版本覆盖的值
+    pub user_user_agents: Vec<(String, String)>,
+    /// 按目标主机（小写 glob）覆盖的 User-Agent，按顺序取第一条匹配的规则，替代全局值
+    pub host_user_agents: Vec<(String, Arc<String>)>,
+    /// 按目标主机缓存 host_user_agents 的匹配结果
+    pub host_rule_cache: HostRuleCache,
+}
+
+impl RewriteConfig {
+    /// 目标主机（域名或 IP，不含端口）匹配的第一条 --host-user-agents 规则
+    pub fn host_user_agent(&self, host: &str) -> Option<&Arc<String>> {
+        if self.host_user_agents.is_empty() {
+            return None;
+        }
+        let host = host.to_ascii_lowercase();
+        let index = self.host_rule_cache.get_or_find(&host, || {
+            self.host_user_agents.iter().position(|(pattern, _)| glob_match(pattern.as_bytes(), host.as_bytes()))
+        });
+        index.map(|index| &self.host_user_agents[index].1)
+    }
+}
 
-pub fn is_http_request(buf: &[u8]) -> bool {
-    buf.starts_with(b"GET ") ||
-        buf.starts_with(b"POST ") ||
-        buf.starts_with(b"HEAD ") ||
-        buf.starts_with(b"PUT ") ||
-        buf.starts_with(b"DELETE ") ||
-        buf.starts_with(b"OPTIONS ") ||
-        buf.starts_with(b"CONNECT ")
+/// 白名单条目与 User-Agent 的匹配方式，均忽略 ASCII 大小写
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhitelistMatch {
+    /// 完全相同
+    #[default]
+    Exact,
+    /// User-Agent 以条目开头，如 `MicroMessenger Client` 匹配 `MicroMessenger Client/8.0`
+    Prefix,
+    /// User-Agent 包含条目
+    Contains,
 }
 
+impl WhitelistMatch {
+    fn matches(self, user_agent: &[u8], item: &[u8]) -> bool {
+        match self {
+            WhitelistMatch::Exact => user_agent.eq_ignore_ascii_case(item),
+            WhitelistMatch::Prefix => user_agent
+                .get(..item.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(item)),
+            // 空模式不匹配任何 User-Agent（windows(0) 也会 panic）
+            WhitelistMatch::Contains => {
+                !item.is_empty() && user_agent.windows(item.len()).any(|window| window.eq_ignore_ascii_case(item))
+            }
+        }
+    }
+}
 
-pub fn modify_user_agent(buf: &mut BytesMut, user_agent: &str) {
-    const USER_AGENT_HEADER: &[u8] = b"User-Agent: ";
+/// 白名单条目，`mode` 为 None 时使用全局匹配方式
+#[derive(Debug)]
+pub struct WhitelistEntry {
+    pub pattern: Box<[u8]>,
+    pub mode: Option<WhitelistMatch>,
+}
 
-    let start = match memmem::find(buf, USER_AGENT_HEADER) {
-        Some(pos) => pos + USER_AGENT_HEADER.len(),
-        None => {
-            error!("未找到 User-Agent 头");
-            return;
+/// 读取白名单文件：每行一个 User-Agent，去掉行尾空白，跳过空行与 `#` 开头的注释行；
+/// 行首的 `exact:`、`prefix:`、`contains:` 为该条目单独指定匹配方式，去掉后为空的行视为错误
+pub fn load_whitelist(path: &Path) -> io::Result<Vec<WhitelistEntry>> {
+    const MODES: &[(&[u8], WhitelistMatch)] = &[
+        (b"exact:", WhitelistMatch::Exact),
+        (b"prefix:", WhitelistMatch::Prefix),
+        (b"contains:", WhitelistMatch::Contains),
+    ];
+
+    let content = std::fs::read(path)?;
+    let mut entries = Vec::new();
+    for (index, line) in content.split(|&c| c == b'\n').enumerate() {
+        let line = line.trim_ascii_end();
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let (pattern, mode) = MODES
+            .iter()
+            .find_map(|&(tag, mode)| line.strip_prefix(tag).map(|rest| (rest, Some(mode))))
+            .unwrap_or((line, None));
+        // 空模式会匹配所有 User-Agent，使改写对所有请求失效
+        if pattern.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("第 {} 行: 匹配方式之后缺少 User-Agent", index + 1),
+            ));
         }
+        entries.push(WhitelistEntry { pattern: Box::from(pattern), mode });
+    }
+    Ok(entries)
+}
+
+/// 读取按目标主机覆盖 User-Agent 的规则文件：每行 `<host-glob> <UA>`，UA 为模式之后的全部内容，
+/// 跳过空行与 `#` 开头的注释行；缺少 UA 的行视为错误
+pub fn load_host_user_agents(path: &Path) -> io::Result<Vec<(String, Arc<String>)>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut rules = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((pattern, user_agent)) = line.split_once(char::is_whitespace) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("第 {} 行: 缺少 User-Agent", index + 1),
+            ));
+        };
+        rules.push((pattern.to_ascii_lowercase(), Arc::new(user_agent.trim_start().to_string())));
+    }
+    Ok(rules)
+}
+
+/// HTTP 请求行：`method SP request-target SP HTTP-version`
+#[derive(Debug)]
+pub struct RequestLine<'a> {
+    pub method: &'a [u8],
+    pub target: &'a [u8],
+    pub version: &'a [u8],
+}
+
+impl RequestLine<'_> {
+    /// 请求目标中的路径部分（去掉查询串与 absolute-form 的 scheme/authority）
+    pub fn path(&self) -> &[u8] {
+        let mut target = self.target;
+        if let Some(pos) = memmem::find(target, b"://") {
+            let rest = &target[pos + 3..];
+            target = match memchr::memchr(b'/', rest) {
+                Some(slash) => &rest[slash..],
+                None => b"/",
+            };
+        }
+        match memchr::memchr(b'?', target) {
+            Some(pos) => &target[..pos],
+            None => target,
+        }
+    }
+}
+
+/// 解析缓冲区开头的请求行，格式不完整时返回 None
+pub fn parse_request_line(buf: &[u8]) -> Option<RequestLine<'_>> {
+    let line_end = memmem::find(buf, b"\r\n")?;
+    let mut parts = buf[..line_end].splitn(3, |&c| c == b' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    let version = parts.next()?;
+    if method.is_empty() || target.is_empty() || version.is_empty() {
+        return None;
+    }
+    Some(RequestLine { method, target, version })
+}
+
+/// 解析 `--version-user-agent` 的 `HTTP/x.y=<UA>`
+pub fn parse_version_user_agent(spec: &str) -> Result<(String, String), String> {
+    let (version, user_agent) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("缺少 `=`，应为 HTTP/x.y=<UA>: {}", spec))?;
+    if !matches!(version.as_bytes(), [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+        if major.is_ascii_digit() && minor.is_ascii_digit())
+    {
+        return Err(format!("无效的 HTTP 版本: {}", version));
+    }
+    check_override_user_agent(version, user_agent)?;
+    Ok((version.to_string(), user_agent.to_string()))
+}
+
+/// 解析 `--user-user-agent` 的 `<用户名>=<UA>`
+pub fn parse_user_user_agent(spec: &str) -> Result<(String, String), String> {
+    let (user, user_agent) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("缺少 `=`，应为 <用户名>=<UA>: {}", spec))?;
+    if user.is_empty() {
+        return Err(format!("用户名不能为空: {}", spec));
+    }
+    check_override_user_agent(user, user_agent)?;
+    Ok((user.to_string(), user_agent.to_string()))
+}
+
+fn check_override_user_agent(key: &str, user_agent: &str) -> Result<(), String> {
+    if user_agent.is_empty() || user_agent.contains(['\r', '\n']) {
+        return Err(format!("{} 的 User-Agent 不能为空或包含换行", key));
+    }
+    Ok(())
+}
+
+/// 请求行是否完整合法：`method SP request-target SP HTTP/x.y CRLF`，且三部分之间恰好各一个空格
+pub fn has_valid_request_line(buf: &[u8]) -> bool {
+    parse_request_line(buf).is_some_and(|line| {
+        matches!(line.version, [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+            if major.is_ascii_digit() && minor.is_ascii_digit())
+    })
+}
+
+/// 请求头是否声明了 body：带有非零的 Content-Length 或任意 Transfer-Encoding
+pub fn expects_body(head: &[u8]) -> bool {
+    head.split(|&c| c == b'\n').skip(1).any(|line| {
+        let Some(colon) = memchr::memchr(b':', line) else {
+            return false;
+        };
+        let (name, value) = (&line[..colon], line[colon + 1..].trim_ascii());
+        (name.eq_ignore_ascii_case(b"Content-Length") && value.iter().any(|&c| c != b'0'))
+            || name.eq_ignore_ascii_case(b"Transfer-Encoding")
+    })
+}
+
+/// 内置识别的 HTTP 方法
+const HTTP_METHODS: &[&str] = &["GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "CONNECT"];
+
+/// 用于识别 HTTP 请求的方法集合，每项都带有方法名后的空格
+#[derive(Debug)]
+pub struct HttpMethods {
+    prefixes: Vec<Box<[u8]>>,
+    /// 方法名忽略大小写匹配（如 `get `）。会增加误判：恰好以 `get ` 等字节开头的
+    /// 二进制流量也会被当作 HTTP 改写，因此默认关闭
+    case_insensitive: bool,
+}
+
+impl HttpMethods {
+    /// 内置方法加上额外配置的方法；方法名必须是非空的 HTTP token
+    pub fn new(extra: &[String], case_insensitive: bool) -> Result<Self, String> {
+        let mut prefixes = Vec::with_capacity(HTTP_METHODS.len() + extra.len());
+        for method in HTTP_METHODS.iter().copied().chain(extra.iter().map(String::as_str)) {
+            let is_token = !method.is_empty()
+                && method.bytes().all(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c));
+            if !is_token {
+                return Err(format!("无效的 HTTP 方法: {:?}", method));
+            }
+            let mut prefix = method.as_bytes().to_vec();
+            prefix.push(b' ');
+            prefixes.push(prefix.into_boxed_slice());
+        }
+        Ok(HttpMethods { prefixes, case_insensitive })
+    }
+
+    /// `buf` 是否可能是请求的开头：已匹配某个方法，或是某个方法（含空格）的前缀
+    pub fn may_start_request(&self, buf: &[u8]) -> bool {
+        is_http_request(buf, self)
+            || self.prefixes.iter().any(|prefix| {
+                prefix.len() > buf.len()
+                    && if self.case_insensitive {
+                        prefix[..buf.len()].eq_ignore_ascii_case(buf)
+                    } else {
+                        prefix.starts_with(buf)
+                    }
+            })
+    }
+
+    /// 首次嗅探需要读取的字节数：足以完整识别最长的方法名及其后的空格
+    pub fn sniff_len(&self) -> usize {
+        self.prefixes.iter().map(|p| p.len()).max().unwrap_or(0)
+    }
+}
+
+impl Default for HttpMethods {
+    fn default() -> Self {
+        HttpMethods::new(&[], false).expect("内置 HTTP 方法均有效")
+    }
+}
+
+/// `buf` 是否以 `methods` 中的某个方法（含其后的空格）开头；只需读入开头几个字节即可判断
+pub fn is_http_request(buf: &[u8], methods: &HttpMethods) -> bool {
+    methods.prefixes.iter().any(|prefix| {
+        if methods.case_insensitive {
+            buf.len() >= prefix.len() && buf[..prefix.len()].eq_ignore_ascii_case(prefix)
+        } else {
+            buf.starts_with(prefix)
+        }
+    })
+}
+
+/// `CONNECT` 请求建立的是隧道，其后多为加密的 TLS 流量，没有可改写的 User-Agent
+pub fn is_connect_request(buf: &[u8], methods: &HttpMethods) -> bool {
+    const PREFIX: &[u8] = b"CONNECT ";
+    if methods.case_insensitive {
+        buf.len() >= PREFIX.len() && buf[..PREFIX.len()].eq_ignore_ascii_case(PREFIX)
+    } else {
+        buf.starts_with(PREFIX)
+    }
+}
+
+
+fn version_user_agent<'a>(buf: &[u8], config: &'a RewriteConfig) -> Option<&'a str> {
+    if config.version_user_agents.is_empty() {
+        return None;
+    }
+    let version = parse_request_line(buf)?.version;
+    config
+        .version_user_agents
+        .iter()
+        .find(|(configured, _)| configured.as_bytes() == version)
+        .map(|(_, user_agent)| user_agent.as_str())
+}
+
+fn path_allowed(buf: &[u8], config: &RewriteConfig) -> bool {
+    if config.rewrite_paths.is_empty() {
+        return true;
+    }
+    let Some(request_line) = parse_request_line(buf) else {
+        debug!("无法解析请求行，跳过路径过滤的 User-Agent 修改");
+        return false;
     };
+    let path = request_line.path();
+    let allowed = config
+        .rewrite_paths
+        .iter()
+        .any(|pattern| glob_match(pattern.as_bytes(), path));
+    if !allowed {
+        debug!("请求路径 {} 不在改写范围内，无需修改。", String::from_utf8_lossy(path));
+    }
+    allowed
+}
+
+const USER_AGENT_HEADER: &[u8] = b"User-Agent";
+
+/// 在请求头中查找头部失败的原因
+enum HeaderError {
+    Missing,
+    Unterminated,
+}
+
+/// 在请求头中定位名为 `name` 的第一个头，返回 (头部行起点, 值起点, 值终点)。
+/// 头部名不区分大小写，值起点跳过冒号后的空格与制表符，值终点为值之后的第一个 `\r`；
+/// 只查找请求行之后、空行之前的头部，不会误匹配 body
+fn find_header(buf: &[u8], name: &[u8]) -> Result<(usize, usize, usize), HeaderError> {
+    let mut line_start = memmem::find(buf, b"\r\n").ok_or(HeaderError::Missing)? + 2;
+    loop {
+        let line = &buf[line_start..];
+        if line.is_empty() || line.starts_with(b"\r\n") {
+            return Err(HeaderError::Missing);
+        }
+        let name_len = name.len();
+        if line.len() > name_len
+            && line[name_len] == b':'
+            && line[..name_len].eq_ignore_ascii_case(name)
+        {
+            let mut start = line_start + name_len + 1;
+            while matches!(buf.get(start), Some(b' ' | b'\t')) {
+                start += 1;
+            }
+            let end = memchr::memchr(b'\r', &buf[start..])
+                .map(|pos| start + pos)
+                .ok_or(HeaderError::Unterminated)?;
+            return Ok((line_start, start, end));
+        }
+        match memmem::find(line, b"\r\n") {
+            Some(pos) => line_start += pos + 2,
+            None => return Err(HeaderError::Missing),
+        }
+    }
+}
+
+impl HeaderError {
+    fn user_agent_reason(&self) -> &'static str {
+        match self {
+            HeaderError::Missing => "未找到 User-Agent 头",
+            HeaderError::Unterminated => "未找到 User-Agent 结束符",
+        }
+    }
+}
+
+fn find_user_agent(buf: &[u8]) -> Result<(usize, usize, usize), &'static str> {
+    find_header(buf, USER_AGENT_HEADER).map_err(|err| err.user_agent_reason())
+}
+
+/// Accept 头是否列出 `text/html`（忽略参数与大小写），用于判断请求是否为浏览器页面导航
+fn accepts_html(buf: &[u8]) -> bool {
+    find_header(buf, b"Accept").is_ok_and(|(_, start, end)| {
+        buf[start..end].split(|&c| c == b',').any(|media_range| {
+            let media_type = media_range.split(|&c| c == b';').next().unwrap_or_default();
+            media_type.trim_ascii().eq_ignore_ascii_case(b"text/html")
+        })
+    })
+}
+
+/// 原始 User-Agent 值中是否含有除 HTAB 外的控制字符（如单独的 `\n`），
+/// 宽松的下游解析器可能把其后的内容当作额外的头部，属于头部注入尝试
+pub fn user_agent_has_control_chars(buf: &[u8]) -> bool {
+    find_user_agent(buf).is_ok_and(|(_, start, end)| {
+        buf[start..end].iter().any(|&c| c.is_ascii_control() && c != b'\t')
+    })
+}
+
+/// [`modify_user_agent`] 对请求做了什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewriteOutcome {
+    /// 未改动请求：不满足改写条件、User-Agent 为空或超长、请求头不完整等
+    Unchanged,
+    /// 请求没有 User-Agent 头且未开启 `insert_missing_ua`，未改动请求；由调用方决定是否记录
+    Missing,
+    /// User-Agent 命中白名单，未改动请求
+    Whitelisted,
+    /// `dry_run` 下本应改写，只记录日志，未改动请求
+    DryRun,
+    /// User-Agent 已被替换、删除或插入
+    Rewritten,
+}
 
-    let end = match memchr::memchr(b'\r', &buf[start..]) {
-        Some(pos) => start + pos,
-        None => {
-            error!("未找到 User-Agent 结束符");
-            return;
+/// 在 `buf` 中的完整请求头里按 `config` 改写 User-Agent，`buf` 的长度随新值变化。
+/// `user` 为连接认证通过的用户名；User-Agent 依次按用户名、HTTP 版本选取，都未配置时使用 `user_agent`
+/// （调用方按目标主机规则或全局值选出）
+pub fn modify_user_agent(buf: &mut BytesMut, user_agent: &str, user: Option<&str>, config: &RewriteConfig) -> RewriteOutcome {
+    if !path_allowed(buf, config) {
+        return RewriteOutcome::Unchanged;
+    }
+    if config.rewrite_on_accept_html && !accepts_html(buf) {
+        debug!("Accept 头未包含 text/html，跳过 User-Agent 修改");
+        return RewriteOutcome::Unchanged;
+    }
+    let user_agent = user
+        .and_then(|user| config.user_user_agents.iter().find(|(configured, _)| configured == user))
+        .map(|(_, user_agent)| user_agent.as_str())
+        .or_else(|| version_user_agent(buf, config))
+        .unwrap_or(user_agent);
+
+    let (header_pos, start, end) = match find_header(buf, USER_AGENT_HEADER) {
+        Ok(found) => found,
+        Err(HeaderError::Missing) if config.insert_missing_ua && !config.remove_ua => {
+            return insert_user_agent(buf, user_agent, config);
+        }
+        Err(HeaderError::Missing) => return RewriteOutcome::Missing,
+        Err(err) => {
+            error!("{}", err.user_agent_reason());
+            return RewriteOutcome::Unchanged;
         }
     };
 
     if end > buf.len() {
         error!("User-Agent 结束符超出缓冲区范围: end={} > buf.len()={}", end, buf.len());
-        return;
+        return RewriteOutcome::Unchanged;
     }
 
     let old_len = end - start;
     let new_len = user_agent.len();
 
-    // 打印修改前的 User-Agent
-    match std::str::from_utf8(&buf[start..end]) {
-        Ok(ua) => debug!("修改前的 User-Agent: {}", ua),
-        Err(_) => error!("修改前的 User-Agent 不是有效的 UTF-8"),
-    };
+    // 空值时 old_len == 0，start == end，下方按同样的偏移拼接即可插入新值
+    if old_len == 0 && config.skip_empty_ua {
+        debug!("User-Agent 为空，按配置跳过修改。");
+        return RewriteOutcome::Unchanged;
+    }
 
     if old_len > 1024 {
         error!("User-Agent 字段超长，无法修改");
-        return;
+        return RewriteOutcome::Unchanged;
     }
 
-    if check_is_in_whitelist(&buf[start..end]) {
+    if check_is_in_whitelist(&buf[start..end], config) {
         debug!("User-Agent 在白名单中，无需修改。");
-        return;
+        return RewriteOutcome::Whitelisted;
     }
 
-    // **修正部分**
-    let mut new_buf = BytesMut::with_capacity(buf.len() - old_len + new_len);
-    new_buf.extend_from_slice(&buf[..start]);  // 复制 User-Agent 之前的部分
-    new_buf.extend_from_slice(user_agent.as_bytes());  // 插入新的 User-Agent
-    new_buf.extend_from_slice(&buf[end..]);  // 复制 User-Agent 之后的部分
+    if !config.rewrite_if_matches.is_empty()
+        && !config
+            .rewrite_if_matches
+            .iter()
+            .any(|pattern| WhitelistMatch::Contains.matches(&buf[start..end], pattern.as_bytes()))
+    {
+        debug!("User-Agent 未匹配 --rewrite-if-matches，无需修改。");
+        return RewriteOutcome::Unchanged;
+    }
 
-    // 替换 buf
-    *buf = new_buf;
+    if config.dry_run {
+        log_dry_run(buf, start, end, (!config.remove_ua).then_some(user_agent));
+        return RewriteOutcome::DryRun;
+    }
+
+    if config.remove_ua {
+        log_rewrite(buf, start, end, None);
+        remove_header_line(buf, header_pos, end);
+        return RewriteOutcome::Rewritten;
+    }
 
-    match std::str::from_utf8(&buf[start..start + new_len]) {
-        Ok(ua) => debug!("User-Agent 已修改为: {}", ua),
-        Err(_) => error!("修改后的 User-Agent 不是有效的 UTF-8"),
+    log_rewrite(buf, start, end, Some(user_agent));
+
+    // 原地替换：先按长度差移动 User-Agent 之后的部分，再写入新值，避免重建整个缓冲区
+    let len = buf.len();
+    if new_len > old_len {
+        buf.resize(len + new_len - old_len, 0);
+        buf.copy_within(end..len, start + new_len);
+    } else if new_len < old_len {
+        buf.copy_within(end..len, start + new_len);
+        buf.truncate(len - (old_len - new_len));
+    }
+    buf[start..start + new_len].copy_from_slice(user_agent.as_bytes());
+    RewriteOutcome::Rewritten
+}
+
+/// 每个被改写的请求只输出一条结构化事件；target/client 来自外层的连接 span。
+/// 字段仅在 debug 启用时才会求值
+fn log_rewrite(buf: &[u8], start: usize, end: usize, new_ua: Option<&str>) {
+    let request_line = parse_request_line(buf);
+    debug!(
+        method = %request_line.as_ref().map_or("-".into(), |line| String::from_utf8_lossy(line.method)),
+        path = %request_line.as_ref().map_or("-".into(), |line| String::from_utf8_lossy(line.path())),
+        old_ua = %display_header_value(&buf[start..end]),
+        new_ua = new_ua.unwrap_or("-"),
+        "{}",
+        if new_ua.is_some() { "User-Agent 已修改" } else { "User-Agent 头已删除" }
+    );
+}
+
+/// 在请求行之后插入 `User-Agent: <user_agent>\r\n`，新头部位于头部块内，不影响 body 的长度
+fn insert_user_agent(buf: &mut BytesMut, user_agent: &str, config: &RewriteConfig) -> RewriteOutcome {
+    let Some(line_end) = memmem::find(buf, b"\r\n").map(|pos| pos + 2) else {
+        return RewriteOutcome::Unchanged;
     };
+    if config.dry_run {
+        log_dry_run(buf, line_end, line_end, Some(user_agent));
+        return RewriteOutcome::DryRun;
+    }
+
+    let header = [USER_AGENT_HEADER, b": ", user_agent.as_bytes(), b"\r\n"].concat();
+    let len = buf.len();
+    buf.resize(len + header.len(), 0);
+    buf.copy_within(line_end..len, line_end + header.len());
+    buf[line_end..line_end + header.len()].copy_from_slice(&header);
+    debug!(new_ua = user_agent, "请求缺少 User-Agent 头，已插入");
+    RewriteOutcome::Rewritten
 }
 
-fn check_is_in_whitelist(buf: &[u8]) -> bool {
-    const WHITELIST: &[&[u8]] = &[
-        b"MicroMessenger Client",
-        b"ByteDancePcdn",
-        b"Go-http-client/1.1",
-        b"Bilibili Freedoooooom/MarkII",
-    ];
-    for &item in WHITELIST {
-        if item.len() == buf.len() && buf.eq_ignore_ascii_case(item) {
-            return true;
+fn log_dry_run(buf: &[u8], start: usize, end: usize, new_ua: Option<&str>) {
+    let request_line = parse_request_line(buf);
+    info!(
+        method = %request_line.as_ref().map_or("-".into(), |line| String::from_utf8_lossy(line.method)),
+        path = %request_line.as_ref().map_or("-".into(), |line| String::from_utf8_lossy(line.path())),
+        old_ua = %display_header_value(&buf[start..end]),
+        new_ua = new_ua.unwrap_or("-"),
+        "[dry-run] {}",
+        if new_ua.is_some() { "User-Agent 将被修改" } else { "User-Agent 头将被删除" }
+    );
+}
+
+/// 头部值用于日志的表示：合法 UTF-8 原样输出，否则逐字节转义（如 `\xff`），
+/// 避免替换字符掩盖客户端实际发送的字节
+fn display_header_value(value: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(value) {
+        Ok(value) => Cow::Borrowed(value),
+        Err(_) => Cow::Owned(format!("{} (非 UTF-8)", value.escape_ascii())),
+    }
+}
+
+/// 删除从 `line_start` 开始、值在 `value_end` 结束的整行头部（含其后的 CRLF）；
+/// 若该头是最后一个头，删除后剩下的 `\r\n` 仍与前一行的 CRLF 组成 `\r\n\r\n`，报文边界不变
+fn remove_header_line(buf: &mut BytesMut, line_start: usize, value_end: usize) {
+    let line_end = if buf[value_end..].starts_with(b"\r\n") {
+        value_end + 2
+    } else {
+        value_end
+    };
+
+    let len = buf.len();
+    buf.copy_within(line_end..len, line_start);
+    buf.truncate(len - (line_end - line_start));
+}
+
+/// `config.whitelist` 为 None 时使用的内置白名单
+pub const BUILTIN_WHITELIST: &[&[u8]] = &[
+    b"MicroMessenger Client",
+    b"ByteDancePcdn",
+    b"Go-http-client/1.1",
+    b"Bilibili Freedoooooom/MarkII",
+];
+
+/// User-Agent 的值 `buf` 是否命中 `config` 的白名单（未配置时为 [`BUILTIN_WHITELIST`]）
+pub fn check_is_in_whitelist(buf: &[u8], config: &RewriteConfig) -> bool {
+    match &config.whitelist {
+        Some(whitelist) => whitelist.iter().any(|entry| {
+            entry.mode.unwrap_or(config.whitelist_match).matches(buf, &entry.pattern)
+        }),
+        None => BUILTIN_WHITELIST.iter().any(|item| config.whitelist_match.matches(buf, item)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以 `UA4F` 为新值改写 `request`，返回结果与改写后的完整字节
+    fn rewrite(request: &[u8], config: &RewriteConfig) -> (RewriteOutcome, Vec<u8>) {
+        let mut buf = BytesMut::from(request);
+        let outcome = modify_user_agent(&mut buf, "UA4F", None, config);
+        (outcome, buf.to_vec())
+    }
+
+    #[test]
+    fn rewrite_path_matching_glob_is_rewritten() {
+        let config = RewriteConfig { rewrite_paths: vec!["/api/*".to_string()], ..RewriteConfig::default() };
+        let (outcome, out) = rewrite(b"GET /api/users?id=1 HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET /api/users?id=1 HTTP/1.1\r\nUser-Agent: UA4F\r\n\r\n");
+
+        // absolute-form 的请求目标按其中的路径匹配
+        let (outcome, _) = rewrite(b"GET http://example.com/api/x HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+    }
+
+    #[test]
+    fn rewrite_path_not_matching_glob_is_left_alone() {
+        let config = RewriteConfig { rewrite_paths: vec!["/api/*".to_string()], ..RewriteConfig::default() };
+        let request = b"GET /static/app.js HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n";
+        let (outcome, out) = rewrite(request, &config);
+        assert_eq!(outcome, RewriteOutcome::Unchanged);
+        assert_eq!(out, request);
+    }
+
+    #[test]
+    fn user_agent_as_first_header() {
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\nHost: x\r\n\r\n", &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent: UA4F\r\nHost: x\r\n\r\n");
+    }
+
+    #[test]
+    fn user_agent_at_buffer_start_is_not_a_header() {
+        // 缓冲区开头是请求行的位置，此处的 `User-Agent:` 不是头部
+        let request = b"User-Agent: curl/8.0\r\nHost: x\r\n\r\n";
+        let (outcome, out) = rewrite(request, &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Missing);
+        assert_eq!(out, request);
+    }
+
+    #[test]
+    fn user_agent_in_the_middle() {
+        let (outcome, out) = rewrite(
+            b"GET / HTTP/1.1\r\nHost: x\r\nUser-Agent: Mozilla/5.0 (X11; Linux x86_64)\r\nAccept: */*\r\n\r\nbody",
+            &RewriteConfig::default(),
+        );
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nHost: x\r\nUser-Agent: UA4F\r\nAccept: */*\r\n\r\nbody");
+    }
+
+    #[test]
+    fn empty_user_agent_gets_the_new_value_by_default() {
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: \r\nHost: x\r\n\r\n", &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent: UA4F\r\nHost: x\r\n\r\n");
+    }
+
+    #[test]
+    fn user_agent_without_trailing_cr_is_left_alone() {
+        let request = b"GET / HTTP/1.1\r\nHost: x\r\nUser-Agent: curl/8.0";
+        let (outcome, out) = rewrite(request, &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Unchanged);
+        assert_eq!(out, request);
+    }
+
+    #[test]
+    fn multi_byte_utf8_values() {
+        let mut buf = BytesMut::from("GET / HTTP/1.1\r\nUser-Agent: 浏览器/1.0\r\nHost: x\r\n\r\n".as_bytes());
+        let outcome = modify_user_agent(&mut buf, "新的代理 ✓", None, &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(&buf[..], "GET / HTTP/1.1\r\nUser-Agent: 新的代理 ✓\r\nHost: x\r\n\r\n".as_bytes());
+    }
+
+    #[test]
+    fn user_agent_of_exactly_1024_bytes_is_rewritten() {
+        let request = format!("GET / HTTP/1.1\r\nUser-Agent: {}\r\nHost: x\r\n\r\n", "a".repeat(1024));
+        let (outcome, out) = rewrite(request.as_bytes(), &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent: UA4F\r\nHost: x\r\n\r\n");
+    }
+
+    #[test]
+    fn user_agent_over_1024_bytes_is_left_alone() {
+        let request = format!("GET / HTTP/1.1\r\nUser-Agent: {}\r\nHost: x\r\n\r\n", "a".repeat(1025));
+        let (outcome, out) = rewrite(request.as_bytes(), &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Unchanged);
+        assert_eq!(out, request.as_bytes());
+    }
+
+    #[test]
+    fn empty_user_agent_skipped_with_skip_empty_ua() {
+        let config = RewriteConfig { skip_empty_ua: true, ..RewriteConfig::default() };
+        let request = b"GET / HTTP/1.1\r\nUser-Agent: \r\nHost: x\r\n\r\n";
+        let (outcome, out) = rewrite(request, &config);
+        assert_eq!(outcome, RewriteOutcome::Unchanged);
+        assert_eq!(out, request);
+
+        // 非空值不受 skip_empty_ua 影响
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent: UA4F\r\n\r\n");
+    }
+
+    #[test]
+    fn remove_ua_deletes_a_middle_header_line() {
+        let config = RewriteConfig { remove_ua: true, ..RewriteConfig::default() };
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\nHost: x\r\n\r\n", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+    }
+
+    #[test]
+    fn remove_ua_keeps_framing_when_it_is_the_last_header() {
+        let config = RewriteConfig { remove_ua: true, ..RewriteConfig::default() };
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nHost: x\r\nUser-Agent: curl/8.0\r\n\r\nbody", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nHost: x\r\n\r\nbody");
+        assert_eq!(memmem::find(&out, b"\r\n\r\n"), Some(b"GET / HTTP/1.1\r\nHost: x".len()));
+    }
+
+    #[test]
+    fn remove_ua_with_only_header() {
+        let config = RewriteConfig { remove_ua: true, ..RewriteConfig::default() };
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn version_user_agents_pick_the_ua_by_request_version() {
+        let config = RewriteConfig {
+            version_user_agents: vec![
+                ("HTTP/1.0".to_string(), "Legacy/1.0".to_string()),
+                ("HTTP/1.1".to_string(), "Modern/2.0".to_string()),
+            ],
+            ..RewriteConfig::default()
+        };
+        let (_, out) = rewrite(b"GET / HTTP/1.0\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(out, b"GET / HTTP/1.0\r\nUser-Agent: Legacy/1.0\r\n\r\n");
+        let (_, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent: Modern/2.0\r\n\r\n");
+    }
+
+    #[test]
+    fn unmapped_version_uses_the_global_ua() {
+        let config = RewriteConfig {
+            version_user_agents: vec![("HTTP/1.0".to_string(), "Legacy/1.0".to_string())],
+            ..RewriteConfig::default()
+        };
+        let (_, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent: UA4F\r\n\r\n");
+    }
+
+    #[test]
+    fn parse_version_user_agent_spec() {
+        assert_eq!(
+            parse_version_user_agent("HTTP/1.0=Legacy/1.0"),
+            Ok(("HTTP/1.0".to_string(), "Legacy/1.0".to_string()))
+        );
+        assert!(parse_version_user_agent("HTTP/1.0").is_err());
+        assert!(parse_version_user_agent("HTTP/2=Modern").is_err());
+    }
+
+    #[test]
+    fn in_place_rewrite_with_shorter_and_equal_values() {
+        let request = b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\nAccept: */*\r\n\r\nbody";
+        let config = RewriteConfig::default();
+        for user_agent in ["ua", "UA4F/1.0"] {
+            let mut buf = BytesMut::from(&request[..]);
+            let capacity = buf.capacity();
+            assert_eq!(modify_user_agent(&mut buf, user_agent, None, &config), RewriteOutcome::Rewritten);
+            let expected = format!("GET / HTTP/1.1\r\nUser-Agent: {user_agent}\r\nAccept: */*\r\n\r\nbody");
+            assert_eq!(&buf[..], expected.as_bytes());
+            // 新值不长于旧值时不需要重新分配
+            assert_eq!(buf.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn grow_rewrite_moves_the_rest_of_the_buffer() {
+        let request = b"GET / HTTP/1.1\r\nUser-Agent: a\r\nAccept: */*\r\n\r\nbody";
+        let user_agent = "Mozilla/5.0 ".repeat(20);
+        let mut buf = BytesMut::from(&request[..]);
+        assert_eq!(modify_user_agent(&mut buf, &user_agent, None, &RewriteConfig::default()), RewriteOutcome::Rewritten);
+        let expected = format!("GET / HTTP/1.1\r\nUser-Agent: {user_agent}\r\nAccept: */*\r\n\r\nbody");
+        assert_eq!(&buf[..], expected.as_bytes());
+    }
+
+    #[test]
+    fn longer_extra_methods_widen_the_sniff() {
+        let builtin = HttpMethods::default();
+        assert_eq!(builtin.sniff_len(), "CONNECT ".len());
+
+        let methods = HttpMethods::new(&["PROPFIND".to_string()], false).unwrap();
+        assert_eq!(methods.sniff_len(), "PROPFIND ".len());
+        let request = b"PROPFIND /dav HTTP/1.1\r\n";
+        assert!(is_http_request(&request[..methods.sniff_len()], &methods));
+        assert!(!is_http_request(&request[..methods.sniff_len()], &builtin));
+        assert!(is_http_request(b"GET / HTTP/1.1\r\n", &methods));
+    }
+
+    #[test]
+    fn extra_methods_must_be_tokens() {
+        assert!(HttpMethods::new(&["BAD METHOD".to_string()], false).is_err());
+        assert!(HttpMethods::new(&[String::new()], false).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8_user_agent_is_replaced_without_touching_neighbours() {
+        let request: &[u8] = b"GET / HTTP/1.1\r\nX-Before: \xc3\x28\r\nUser-Agent: bad\xff\xfe\x80/1.0\r\nX-After: \xa0\xa1\r\n\r\n\xff";
+        let (outcome, out) = rewrite(request, &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nX-Before: \xc3\x28\r\nUser-Agent: UA4F\r\nX-After: \xa0\xa1\r\n\r\n\xff");
+    }
+
+    #[test]
+    fn invalid_utf8_values_are_escaped_for_logging() {
+        assert_eq!(display_header_value(b"curl/8.4.0"), "curl/8.4.0");
+        assert_eq!(display_header_value(b"bad\xff/1.0"), "bad\\xff/1.0 (非 UTF-8)");
+    }
+
+    #[test]
+    fn lowercase_methods_only_match_when_case_insensitive() {
+        let strict = HttpMethods::default();
+        let relaxed = HttpMethods::new(&[], true).unwrap();
+        let request = b"get / HTTP/1.1\r\nUser-Agent: x\r\n\r\n";
+
+        assert!(!is_http_request(request, &strict));
+        assert!(!strict.may_start_request(b"ge"));
+        assert!(is_http_request(request, &relaxed));
+        assert!(relaxed.may_start_request(b"ge"));
+        assert!(is_http_request(b"GET / HTTP/1.1\r\n", &relaxed));
+        assert!(is_connect_request(b"connect example.com:443 HTTP/1.1\r\n", &relaxed));
+        assert!(!is_connect_request(b"connect example.com:443 HTTP/1.1\r\n", &strict));
+        // 方法名后必须有空格，避免匹配 `gets` 这类前缀相同的数据
+        assert!(!is_http_request(b"gets", &relaxed));
+    }
+
+    #[test]
+    fn request_line_needs_target_and_http_version() {
+        assert!(has_valid_request_line(b"GET /index.html HTTP/1.1\r\nHost: a\r\n\r\n"));
+        assert!(has_valid_request_line(b"OPTIONS * HTTP/1.0\r\n\r\n"));
+
+        assert!(!has_valid_request_line(b"GET /index.html\r\n\r\n"));
+        assert!(!has_valid_request_line(b"GET  /index.html HTTP/1.1\r\n\r\n"));
+        assert!(!has_valid_request_line(b"GET /index.html HTTP/x.1\r\n\r\n"));
+        assert!(!has_valid_request_line(b"GET /index.html FTP/1.1\r\n\r\n"));
+        assert!(!has_valid_request_line(b"GET garbage"));
+    }
+
+    #[test]
+    fn lowercase_and_mixed_case_header_names_are_rewritten() {
+        for name in ["user-agent", "USER-AGENT", "uSeR-aGeNt"] {
+            let request = format!("GET / HTTP/1.1\r\nHost: x\r\n{name}: curl/8.0\r\nAccept: */*\r\n\r\n");
+            let (outcome, out) = rewrite(request.as_bytes(), &RewriteConfig::default());
+            assert_eq!(outcome, RewriteOutcome::Rewritten, "{name}");
+            let expected = format!("GET / HTTP/1.1\r\nHost: x\r\n{name}: UA4F\r\nAccept: */*\r\n\r\n");
+            assert_eq!(out, expected.as_bytes(), "{name}");
+        }
+    }
+
+    #[test]
+    fn optional_whitespace_before_the_value_is_tolerated() {
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nUser-Agent:  curl/8.0\r\nHost: x\r\n\r\n", &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nUser-Agent:  UA4F\r\nHost: x\r\n\r\n");
+
+        let (outcome, out) = rewrite(b"GET / HTTP/1.1\r\nuser-agent:\tcurl/8.0\r\nHost: x\r\n\r\n", &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nuser-agent:\tUA4F\r\nHost: x\r\n\r\n");
+    }
+
+    #[test]
+    fn header_names_ending_in_user_agent_are_not_matched() {
+        let request = b"GET / HTTP/1.1\r\nX-User-Agent: curl/8.0\r\nHost: x\r\n\r\n";
+        let (outcome, out) = rewrite(request, &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Missing);
+        assert_eq!(out, request);
+    }
+
+    #[test]
+    fn user_user_agents_take_precedence_over_version_and_global_values() {
+        let config = RewriteConfig {
+            user_user_agents: vec![("mobile".to_string(), "Mobile/1.0".to_string())],
+            version_user_agents: vec![("HTTP/1.1".to_string(), "Modern/2.0".to_string())],
+            ..RewriteConfig::default()
+        };
+        let request: &[u8] = b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n";
+        let rewrite_as = |user| {
+            let mut buf = BytesMut::from(request);
+            modify_user_agent(&mut buf, "UA4F", user, &config);
+            buf.to_vec()
+        };
+
+        assert_eq!(rewrite_as(Some("mobile")), b"GET / HTTP/1.1\r\nUser-Agent: Mobile/1.0\r\n\r\n");
+        assert_eq!(rewrite_as(Some("desktop")), b"GET / HTTP/1.1\r\nUser-Agent: Modern/2.0\r\n\r\n");
+        assert_eq!(rewrite_as(None), b"GET / HTTP/1.1\r\nUser-Agent: Modern/2.0\r\n\r\n");
+    }
+
+    #[test]
+    fn parse_user_user_agent_spec() {
+        assert_eq!(parse_user_user_agent("mobile=Mobile/1.0 (a=b)").unwrap(), ("mobile".to_string(), "Mobile/1.0 (a=b)".to_string()));
+        assert!(parse_user_user_agent("mobile").is_err());
+        assert!(parse_user_user_agent("=Mobile/1.0").is_err());
+        assert!(parse_user_user_agent("mobile=").is_err());
+        assert!(parse_user_user_agent("mobile=a\r\nX-Injected: 1").is_err());
+    }
+
+    #[test]
+    fn rewrite_on_accept_html_only_rewrites_navigations() {
+        let config = RewriteConfig { rewrite_on_accept_html: true, ..RewriteConfig::default() };
+
+        let navigation = b"GET / HTTP/1.1\r\nAccept: text/html,application/xhtml+xml;q=0.9,*/*;q=0.8\r\nUser-Agent: curl/8.0\r\n\r\n";
+        let (outcome, out) = rewrite(navigation, &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+        assert_eq!(out, b"GET / HTTP/1.1\r\nAccept: text/html,application/xhtml+xml;q=0.9,*/*;q=0.8\r\nUser-Agent: UA4F\r\n\r\n");
+
+        // 媒体类型忽略大小写与参数
+        let (outcome, _) = rewrite(b"GET / HTTP/1.1\r\naccept: Text/HTML; q=1\r\nUser-Agent: curl/8.0\r\n\r\n", &config);
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+
+        for request in [
+            &b"GET /api HTTP/1.1\r\nAccept: application/json\r\nUser-Agent: curl/8.0\r\n\r\n"[..],
+            b"GET /api HTTP/1.1\r\nUser-Agent: curl/8.0\r\n\r\n",
+            b"GET /api HTTP/1.1\r\nAccept: text/html-fragment\r\nUser-Agent: curl/8.0\r\n\r\n",
+        ] {
+            let (outcome, out) = rewrite(request, &config);
+            assert_eq!(outcome, RewriteOutcome::Unchanged);
+            assert_eq!(out, request);
+        }
+    }
+
+    #[test]
+    fn requests_without_text_html_are_rewritten_without_the_flag() {
+        let (outcome, _) = rewrite(b"GET /api HTTP/1.1\r\nAccept: application/json\r\nUser-Agent: curl/8.0\r\n\r\n", &RewriteConfig::default());
+        assert_eq!(outcome, RewriteOutcome::Rewritten);
+    }
+
+    #[test]
+    fn embedded_newline_in_the_user_agent_is_detected() {
+        assert!(user_agent_has_control_chars(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\nX-Injected: 1\r\nHost: x\r\n\r\n"));
+        assert!(user_agent_has_control_chars(b"GET / HTTP/1.1\r\nuser-agent: a\x00b\r\n\r\n"));
+        // HTAB 是合法的头部值字符
+        assert!(!user_agent_has_control_chars(b"GET / HTTP/1.1\r\nUser-Agent: a\tb\r\n\r\n"));
+        assert!(!user_agent_has_control_chars(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.0\r\nX-Other: a\nb\r\n\r\n"));
+        assert!(!user_agent_has_control_chars(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n"));
+    }
+
+    #[test]
+    fn whitelist_lines_with_an_empty_pattern_are_rejected() {
+        let path = std::env::temp_dir().join(format!("ua4f-whitelist-{}.txt", std::process::id()));
+        std::fs::write(&path, "# 注释\nexact:MyApp/1.0\nprefix:MicroMessenger Client\n\ncontains:\n").unwrap();
+        let err = load_whitelist(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("第 5 行"), "{}", err);
+
+        std::fs::write(&path, "exact:MyApp/1.0\nprefix:MicroMessenger Client\nMyOtherApp\n").unwrap();
+        let whitelist = load_whitelist(&path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(whitelist.len(), 3);
+        let config = RewriteConfig { whitelist: Some(whitelist), ..RewriteConfig::default() };
+        assert!(check_is_in_whitelist(b"MicroMessenger Client/8.0", &config));
+        assert!(!check_is_in_whitelist(b"curl/8.4.0", &config));
+    }
+
+    #[test]
+    fn cached_host_rules_return_the_first_matching_rule() {
+        let rule = |pattern: &str, user_agent: &str| (pattern.to_string(), Arc::new(user_agent.to_string()));
+        let config = RewriteConfig {
+            host_user_agents: vec![rule("api.example.com", "Api/1.0"), rule("*.example.com", "Site/1.0")],
+            ..RewriteConfig::default()
+        };
+        // 第二次查询命中缓存，结果与首次匹配相同；主机名不区分大小写
+        for _ in 0..2 {
+            assert_eq!(config.host_user_agent("API.example.com").map(|ua| ua.as_str()), Some("Api/1.0"));
+            assert_eq!(config.host_user_agent("www.example.com").map(|ua| ua.as_str()), Some("Site/1.0"));
+            assert_eq!(config.host_user_agent("example.org"), None);
         }
     }
-    false
 }
@@ -1 +1,3 @@
-pub mod utils;
\ No newline at end of file
+pub mod http;
+pub mod resolver;
+pub mod utils;
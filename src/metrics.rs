@@ -0,0 +1,148 @@
+use std::fmt::Write as _;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::RuntimeFlavor;
+use tracing::{debug, warn};
+use crate::stats;
+
+/// 读取抓取请求头的超时时间与最大长度，防止空闲或异常连接占用端点
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// 在 `listener` 上提供 Prometheus 文本格式的 `GET /metrics`；
+/// `non_http_cache_entries` 返回当前非 HTTP 缓存条目数。
+/// 中止返回的任务即关闭监听套接字，已接受的请求照常处理完
+pub fn spawn(listener: TcpListener, non_http_cache_entries: fn() -> u64) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!("指标端点 accept 失败: {}", err);
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                if let Err(err) = serve(stream, non_http_cache_entries).await {
+                    debug!("指标请求处理失败: {}", err);
+                }
+            });
+        }
+    })
+}
+
+async fn serve(mut stream: TcpStream, non_http_cache_entries: fn() -> u64) -> std::io::Result<()> {
+    let mut request = Vec::with_capacity(512);
+    let read_head = async {
+        let mut buf = [0u8; 512];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_LEN {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    if tokio::time::timeout(REQUEST_TIMEOUT, read_head).await.is_err() {
+        return Ok(());
+    }
+
+    let request_line = request.split(|&c| c == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|&c| c == b' ');
+    let response = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => {
+            let body = render(non_http_cache_entries());
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn render(non_http_cache_entries: u64) -> String {
+    let metrics: [(&str, &str, &str, u64); 9] = [
+        ("ua4f_connections_total", "counter", "启动以来接受的连接总数", stats::total_connections()),
+        ("ua4f_active_connections", "gauge", "当前活跃连接数", stats::active_connections() as u64),
+        ("ua4f_upload_bytes_total", "counter", "已结束连接的上行字节数", stats::TOTAL_UP.load(Ordering::Relaxed)),
+        ("ua4f_download_bytes_total", "counter", "已结束连接的下行字节数", stats::TOTAL_DOWN.load(Ordering::Relaxed)),
+        ("ua4f_ua_rewrites_total", "counter", "User-Agent 被改写或删除的请求数", stats::UA_REWRITES.load(Ordering::Relaxed)),
+        ("ua4f_dry_run_rewrites_total", "counter", "--dry-run 下本应改写 User-Agent 的请求数", stats::DRY_RUN_REWRITES.load(Ordering::Relaxed)),
+        ("ua4f_whitelist_hits_total", "counter", "User-Agent 命中白名单的请求数", stats::WHITELIST_HITS.load(Ordering::Relaxed)),
+        ("ua4f_malformed_handshakes_total", "counter", "SOCKS5 协议错误导致失败的握手数", stats::malformed_handshakes()),
+        ("ua4f_non_http_cache_entries", "gauge", "非 HTTP 缓存条目数", non_http_cache_entries),
+    ];
+
+    let mut body = String::new();
+    for (name, kind, help, value) in metrics {
+        let _ = writeln!(body, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}");
+    }
+
+    // 运行时的实际配置，用于确认 --threads 已生效
+    let runtime = tokio::runtime::Handle::current();
+    let flavor = match runtime.runtime_flavor() {
+        RuntimeFlavor::CurrentThread => "current_thread",
+        RuntimeFlavor::MultiThread => "multi_thread",
+        _ => "unknown",
+    };
+    let _ = writeln!(
+        body,
+        "# HELP ua4f_worker_threads tokio 运行时的工作线程数\n# TYPE ua4f_worker_threads gauge\nua4f_worker_threads {}",
+        runtime.metrics().num_workers()
+    );
+    let _ = writeln!(
+        body,
+        "# HELP ua4f_runtime_info tokio 运行时类型\n# TYPE ua4f_runtime_info gauge\nua4f_runtime_info{{flavor=\"{flavor}\"}} 1"
+    );
+
+    // 按监听地址细分的连接数，标签为监听地址
+    let listeners = stats::listeners();
+    let per_listener = [
+        (
+            "ua4f_listener_connections_total",
+            "counter",
+            "各监听地址启动以来接受的连接总数",
+            listeners.iter().map(|listener| listener.total_connections()).collect::<Vec<_>>(),
+        ),
+        (
+            "ua4f_listener_active_connections",
+            "gauge",
+            "各监听地址当前活跃连接数",
+            listeners.iter().map(|listener| listener.active_connections() as u64).collect(),
+        ),
+    ];
+    for (name, kind, help, values) in per_listener {
+        let _ = writeln!(body, "# HELP {name} {help}\n# TYPE {name} {kind}");
+        for (listener, value) in listeners.iter().zip(values) {
+            let _ = writeln!(body, "{name}{{listener=\"{}\"}} {value}", listener.name);
+        }
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_configured_worker_threads() {
+        let runtime = crate::build_runtime(3).unwrap();
+        let body = runtime.block_on(async { render(0) });
+        assert!(body.contains("\nua4f_worker_threads 3\n"), "{}", body);
+        assert!(body.contains("\nua4f_runtime_info{flavor=\"multi_thread\"} 1\n"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn reports_the_current_thread_runtime() {
+        let body = render(0);
+        assert!(body.contains("\nua4f_worker_threads 1\n"), "{}", body);
+        assert!(body.contains("\nua4f_runtime_info{flavor=\"current_thread\"} 1\n"), "{}", body);
+    }
+}
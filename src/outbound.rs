@@ -0,0 +1,582 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use base64::Engine;
+use socks5_server::proto::Address;
+use ua4f::utils::circuit_breaker::{BreakerState, CircuitBreaker};
+use ua4f::utils::glob::glob_match;
+use ua4f::utils::rule_cache::HostRuleCache;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
+use tracing::{info, warn};
+
+/// HTTP CONNECT 上游代理响应头的最大长度
+const MAX_UPSTREAM_RESPONSE_LEN: usize = 8 * 1024;
+
+/// 出站（到目标）连接的套接字选项
+#[derive(Debug)]
+pub struct OutboundConfig {
+    /// 域名解析的超时时间，与建立 TCP 连接的超时分开计算
+    pub dns_timeout: Duration,
+    /// 建立 TCP 连接（经由上游代理时含 CONNECT 握手）的超时时间
+    pub connect_timeout: Duration,
+    /// 为出站连接设置的 SO_MARK，用于配合 iptables/nftables 做策略路由
+    #[cfg(target_os = "linux")]
+    pub mark: Option<u32>,
+    /// 经由 HTTP CONNECT 上游代理连接目标
+    pub upstream_http: Option<UpstreamHttp>,
+    /// 按目标主机覆盖的套接字选项，按顺序取第一条匹配的规则
+    pub socket_rules: Vec<SocketRule>,
+    /// 按目标主机缓存 socket_rules 的匹配结果
+    pub socket_rule_cache: HostRuleCache,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        OutboundConfig {
+            dns_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(30),
+            #[cfg(target_os = "linux")]
+            mark: None,
+            upstream_http: None,
+            socket_rules: Vec::new(),
+            socket_rule_cache: HostRuleCache::default(),
+        }
+    }
+}
+
+/// 按目标主机覆盖的套接字选项，未指定的项沿用默认值（开启 TCP_NODELAY、不启用 keepalive）
+#[derive(Debug)]
+pub struct SocketRule {
+    /// 目标主机（域名或 IP，不含端口）的 glob 模式，不区分大小写
+    pub pattern: String,
+    pub nodelay: Option<bool>,
+    /// TCP keepalive 的空闲探测时间，`Some(Duration::ZERO)` 表示显式关闭
+    pub keepalive: Option<Duration>,
+}
+
+/// 读取套接字选项规则文件：每行 `<host-glob> [nodelay=on|off] [keepalive=<秒>]`，
+/// 跳过空行与 `#` 开头的注释行；无法解析的行视为错误
+pub fn load_socket_rules(path: &Path) -> io::Result<Vec<SocketRule>> {
+    let content = std::fs::read_to_string(path)?;
+    let invalid = |index: usize, reason: String| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("第 {} 行: {}", index + 1, reason))
+    };
+
+    let mut rules = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let pattern = fields.next().unwrap_or_default().to_ascii_lowercase();
+        let mut rule = SocketRule { pattern, nodelay: None, keepalive: None };
+        for field in fields {
+            match field.split_once('=') {
+                Some(("nodelay", "on")) => rule.nodelay = Some(true),
+                Some(("nodelay", "off")) => rule.nodelay = Some(false),
+                Some(("keepalive", secs)) => {
+                    let secs: u64 = secs.parse().map_err(|_| invalid(index, format!("无效的 keepalive: {}", secs)))?;
+                    rule.keepalive = Some(Duration::from_secs(secs));
+                }
+                _ => return Err(invalid(index, format!("无法识别的选项: {}", field))),
+            }
+        }
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+/// 连接目标失败的原因，区分域名解析与建立连接两个阶段
+#[derive(Debug)]
+pub enum ConnectError {
+    Resolve(io::Error),
+    ResolveTimeout,
+    Connect(io::Error),
+    ConnectTimeout,
+    /// 目标地址无法安全地写入 CONNECT 请求，例如域名含控制字符、空格或 `@`
+    InvalidTarget(String),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::Resolve(err) => write!(f, "域名解析失败: {}", err),
+            ConnectError::ResolveTimeout => write!(f, "域名解析超时"),
+            ConnectError::Connect(err) => write!(f, "连接失败: {}", err),
+            ConnectError::ConnectTimeout => write!(f, "连接超时"),
+            ConnectError::InvalidTarget(reason) => write!(f, "目标地址无效: {}", reason),
+        }
+    }
+}
+
+impl From<ConnectError> for io::Error {
+    fn from(err: ConnectError) -> Self {
+        match err {
+            ConnectError::Resolve(err) | ConnectError::Connect(err) => err,
+            ConnectError::ResolveTimeout | ConnectError::ConnectTimeout => {
+                io::Error::new(io::ErrorKind::TimedOut, err.to_string())
+            }
+            ConnectError::InvalidTarget(_) => io::Error::new(io::ErrorKind::InvalidInput, err.to_string()),
+        }
+    }
+}
+
+/// 支持 CONNECT 方法的 HTTP 上游代理
+#[derive(Debug)]
+pub struct UpstreamHttp {
+    /// 代理地址，`host:port`
+    pub addr: String,
+    /// 预先编码好的 `Proxy-Authorization` 头的值
+    authorization: Option<String>,
+    /// 连续失败后暂停使用代理的熔断器，None 表示不启用
+    breaker: Option<CircuitBreaker>,
+    /// 熔断期间改为直连目标，否则直接失败
+    fallback_direct: bool,
+}
+
+/// 上游代理正常响应但拒绝了 CONNECT，说明代理本身可用，不计入熔断失败
+#[derive(Debug)]
+struct UpstreamRefused(String);
+
+impl fmt::Display for UpstreamRefused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UpstreamRefused {}
+
+impl UpstreamHttp {
+    /// `credentials` 为 `user:pass`，以 Basic 方式认证
+    pub fn new(addr: String, credentials: Option<&str>) -> Self {
+        let authorization = credentials.map(|credentials| {
+            format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials))
+        });
+        UpstreamHttp { addr, authorization, breaker: None, fallback_direct: false }
+    }
+
+    /// 启用熔断：连续失败 `threshold` 次后暂停使用代理 `cooldown`，期间直连（`fallback_direct`）或直接失败
+    pub fn with_breaker(mut self, threshold: u32, cooldown: Duration, fallback_direct: bool) -> Self {
+        self.breaker = Some(CircuitBreaker::new(threshold, cooldown));
+        self.fallback_direct = fallback_direct;
+        self
+    }
+
+    /// 经由熔断器连接：熔断期间返回 None，由调用方决定直连还是失败
+    async fn connect_guarded(&self, target: &Address, config: &OutboundConfig) -> Option<Result<TcpStream, ConnectError>> {
+        // 先于熔断器检查，无效目标与代理是否可用无关，不应占用探测名额
+        if let Err(err) = check_connect_target(target) {
+            return Some(Err(err));
+        }
+        let Some(breaker) = &self.breaker else {
+            return Some(self.connect(target, config).await);
+        };
+        let (allowed, transition) = breaker.try_acquire();
+        if transition == Some(BreakerState::HalfOpen) {
+            info!("上游代理 {} 冷却结束，放行一个探测连接", self.addr);
+        }
+        if !allowed {
+            return None;
+        }
+
+        let result = self.connect(target, config).await;
+        let healthy = match &result {
+            Ok(_) => true,
+            Err(ConnectError::Connect(err)) => err.get_ref().is_some_and(|err| err.is::<UpstreamRefused>()),
+            Err(_) => false,
+        };
+        match breaker.record(healthy) {
+            Some(BreakerState::Open) => warn!(
+                "上游代理 {} 连续失败，暂停使用{}",
+                self.addr,
+                if self.fallback_direct { "，改为直连目标" } else { "" }
+            ),
+            Some(BreakerState::Closed) => info!("上游代理 {} 已恢复", self.addr),
+            _ => {}
+        }
+        Some(result)
+    }
+
+    /// 通过上游代理建立到 `target` 的隧道，非 200 响应视为连接失败
+    async fn connect(&self, target: &Address, config: &OutboundConfig) -> Result<TcpStream, ConnectError> {
+        let addrs = resolve(self.addr.as_str(), config).await?;
+        with_connect_timeout(config, async {
+            let stream = connect_any(addrs, config, &self.addr).await?;
+            self.handshake(stream, target).await
+        })
+        .await
+    }
+
+    async fn handshake(&self, mut stream: TcpStream, target: &Address) -> io::Result<TcpStream> {
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some(authorization) = &self.authorization {
+            request.push_str("Proxy-Authorization: ");
+            request.push_str(authorization);
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // 逐字节读取响应头，避免吞掉隧道建立后目标先发送的数据
+        let mut response = Vec::with_capacity(256);
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_UPSTREAM_RESPONSE_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "上游代理响应头过长"));
+            }
+            response.push(stream.read_u8().await?);
+        }
+
+        let status_line = response.split(|&c| c == b'\r').next().unwrap_or_default();
+        let status = status_line.split(|&c| c == b' ').nth(1);
+        if !status_line.starts_with(b"HTTP/1.") || status != Some(b"200") {
+            return Err(io::Error::other(UpstreamRefused(format!(
+                "上游代理 {} 拒绝 CONNECT {}: {}",
+                self.addr,
+                target,
+                String::from_utf8_lossy(status_line)
+            ))));
+        }
+        Ok(stream)
+    }
+}
+
+/// 域名会原样写入 CONNECT 请求行与 Host 头，含控制字符或空格可注入额外的请求头，
+/// 含 `@` 可能被代理当作 userinfo 解析到其他主机
+fn check_connect_target(target: &Address) -> Result<(), ConnectError> {
+    let Address::DomainAddress(domain, _) = target else {
+        return Ok(());
+    };
+    if domain.is_empty() {
+        return Err(ConnectError::InvalidTarget("域名为空".to_string()));
+    }
+    if domain.iter().any(|&c| c <= b' ' || c == 0x7f || c == b'@') {
+        return Err(ConnectError::InvalidTarget(format!(
+            "域名含非法字符: {:?}",
+            String::from_utf8_lossy(domain)
+        )));
+    }
+    Ok(())
+}
+
+impl OutboundConfig {
+    fn apply(&self, socket: &TcpSocket) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let Some(mark) = self.mark {
+            socket2::SockRef::from(socket).set_mark(mark).map_err(|err| {
+                io::Error::new(err.kind(), format!("设置 SO_MARK={} 失败（需要 CAP_NET_ADMIN）: {}", mark, err))
+            })?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = socket;
+        Ok(())
+    }
+
+    /// 为已建立的目标连接设置 TCP_NODELAY 与 keepalive，`host` 为目标域名或 IP
+    pub fn tune_stream(&self, stream: &TcpStream, host: &str) -> io::Result<()> {
+        let rule = if self.socket_rules.is_empty() {
+            None
+        } else {
+            let host = host.to_ascii_lowercase();
+            let index = self.socket_rule_cache.get_or_find(&host, || {
+                self.socket_rules.iter().position(|rule| glob_match(rule.pattern.as_bytes(), host.as_bytes()))
+            });
+            index.map(|index| &self.socket_rules[index])
+        };
+
+        stream.set_nodelay(rule.and_then(|rule| rule.nodelay).unwrap_or(true))?;
+        match rule.and_then(|rule| rule.keepalive) {
+            Some(Duration::ZERO) => socket2::SockRef::from(stream).set_keepalive(false)?,
+            Some(time) => {
+                let keepalive = socket2::TcpKeepalive::new().with_time(time);
+                socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// 启动时检查选项能否生效，例如权限不足无法设置 SO_MARK
+    pub fn validate(&self) -> io::Result<()> {
+        self.apply(&TcpSocket::new_v4()?)
+    }
+}
+
+/// 在 dns_timeout 内完成域名解析
+async fn resolve(
+    host: impl tokio::net::ToSocketAddrs,
+    config: &OutboundConfig,
+) -> Result<Vec<SocketAddr>, ConnectError> {
+    resolve_with(tokio::net::lookup_host(host), config).await
+}
+
+/// 在 dns_timeout 内等待 `lookup` 完成；与 connect_timeout 无关，便于单独替换解析过程
+async fn resolve_with<I: IntoIterator<Item = SocketAddr>>(
+    lookup: impl std::future::Future<Output = io::Result<I>>,
+    config: &OutboundConfig,
+) -> Result<Vec<SocketAddr>, ConnectError> {
+    match tokio::time::timeout(config.dns_timeout, lookup).await {
+        Ok(Ok(addrs)) => Ok(addrs.into_iter().collect()),
+        Ok(Err(err)) => Err(ConnectError::Resolve(err)),
+        Err(_) => Err(ConnectError::ResolveTimeout),
+    }
+}
+
+async fn with_connect_timeout(
+    config: &OutboundConfig,
+    connect: impl std::future::Future<Output = io::Result<TcpStream>>,
+) -> Result<TcpStream, ConnectError> {
+    match tokio::time::timeout(config.connect_timeout, connect).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(err)) => Err(ConnectError::Connect(err)),
+        Err(_) => Err(ConnectError::ConnectTimeout),
+    }
+}
+
+async fn connect_addr(addr: SocketAddr, config: &OutboundConfig) -> io::Result<TcpStream> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    config.apply(&socket)?;
+    socket.connect(addr).await
+}
+
+/// 依次尝试解析出的每个地址，全部失败时返回最后一个错误
+async fn connect_any(
+    addrs: Vec<SocketAddr>,
+    config: &OutboundConfig,
+    host: &str,
+) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addrs {
+        match connect_addr(addr, config).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("无法解析域名 {}", host))
+    }))
+}
+
+/// 按配置建立到目标地址的连接：先在 dns_timeout 内解析域名，再在 connect_timeout 内建立连接；
+/// 配置了上游代理时经由代理建立隧道，代理熔断期间按配置直连或直接失败
+pub async fn connect(addr: &Address, config: &OutboundConfig) -> Result<TcpStream, ConnectError> {
+    if let Some(upstream) = &config.upstream_http {
+        match upstream.connect_guarded(addr, config).await {
+            Some(result) => return result,
+            None if upstream.fallback_direct => {}
+            None => {
+                return Err(ConnectError::Connect(io::Error::other(format!(
+                    "上游代理 {} 暂停使用中",
+                    upstream.addr
+                ))))
+            }
+        }
+    }
+    match addr {
+        Address::SocketAddress(addr) => with_connect_timeout(config, connect_addr(*addr, config)).await,
+        Address::DomainAddress(domain, port) => {
+            let domain = String::from_utf8_lossy(domain);
+            let addrs = resolve((domain.as_ref(), *port), config).await?;
+            if addrs.is_empty() {
+                return Err(ConnectError::Resolve(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("无法解析域名 {}", domain),
+                )));
+            }
+            with_connect_timeout(config, connect_any(addrs, config, &domain)).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// 本地 HTTP CONNECT 代理：接受一个连接，读取完整请求头后回复 `response`，返回收到的请求头
+    async fn spawn_proxy(response: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.write_all(b"tunnel").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (addr, handle)
+    }
+
+    fn upstream_config(upstream: UpstreamHttp) -> OutboundConfig {
+        OutboundConfig { upstream_http: Some(upstream), ..OutboundConfig::default() }
+    }
+
+    fn domain(host: &[u8]) -> Address {
+        Address::DomainAddress(host.to_vec(), 443)
+    }
+
+    #[tokio::test]
+    async fn connect_through_upstream_proxy() {
+        let (addr, proxy) = spawn_proxy("HTTP/1.1 200 Connection established\r\n\r\n").await;
+        let config = upstream_config(UpstreamHttp::new(addr, Some("user:pass")));
+
+        let mut stream = connect(&domain(b"example.com"), &config).await.unwrap();
+        // 响应头之后的数据属于隧道，不能被握手读走
+        let mut tunnel = [0; 6];
+        stream.read_exact(&mut tunnel).await.unwrap();
+        assert_eq!(&tunnel, b"tunnel");
+
+        assert_eq!(
+            proxy.await.unwrap(),
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\
+             Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn non_200_response_is_a_refusal() {
+        let (addr, proxy) = spawn_proxy("HTTP/1.1 403 Forbidden\r\n\r\n").await;
+        let config = upstream_config(UpstreamHttp::new(addr, None));
+
+        let err = match connect(&domain(b"example.com"), &config).await {
+            Err(ConnectError::Connect(err)) => err,
+            other => panic!("意外的结果: {:?}", other),
+        };
+        assert!(err.get_ref().is_some_and(|err| err.is::<UpstreamRefused>()));
+        proxy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_domains_are_rejected_before_contacting_the_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let config = upstream_config(UpstreamHttp::new(addr, None));
+
+        for host in [&b"evil.com\r\nX-Injected: 1"[..], b"a b.com", b"user@evil.com", b"a\x7f.com", b""] {
+            let result = connect(&domain(host), &config).await;
+            assert!(matches!(result, Err(ConnectError::InvalidTarget(_))), "{:?}", host);
+        }
+        // 代理从未收到连接
+        let accepted = tokio::time::timeout(Duration::from_millis(50), listener.accept()).await;
+        assert!(accepted.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn outbound_sockets_carry_the_configured_mark() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = Address::SocketAddress(listener.local_addr().unwrap());
+        let config = OutboundConfig { mark: Some(0x2a), ..OutboundConfig::default() };
+
+        let stream = match connect(&target, &config).await {
+            Ok(stream) => stream,
+            // 没有 CAP_NET_ADMIN 时无法设置 SO_MARK，无从验证
+            Err(ConnectError::Connect(err)) if err.kind() == io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("{err}"),
+        };
+        assert_eq!(socket2::SockRef::from(&stream).mark().unwrap(), 0x2a);
+
+        let unmarked = connect(&target, &OutboundConfig::default()).await.unwrap();
+        assert_eq!(socket2::SockRef::from(&unmarked).mark().unwrap(), 0);
+    }
+
+    /// 耗时 `delay` 后解析出 `addr` 的解析桩
+    async fn slow_lookup(delay: Duration, addr: SocketAddr) -> io::Result<Vec<SocketAddr>> {
+        tokio::time::sleep(delay).await;
+        Ok(vec![addr])
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dns_timeout_fires_independently_of_the_connect_timeout() {
+        let config = OutboundConfig {
+            dns_timeout: Duration::from_secs(1),
+            connect_timeout: Duration::from_secs(60),
+            ..OutboundConfig::default()
+        };
+        let addr = SocketAddr::from(([127, 0, 0, 1], 80));
+
+        let started = tokio::time::Instant::now();
+        let result = resolve_with(slow_lookup(Duration::from_secs(10), addr), &config).await;
+        assert!(matches!(result, Err(ConnectError::ResolveTimeout)), "{result:?}");
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_resolution_within_the_dns_timeout_ignores_the_connect_timeout() {
+        let config = OutboundConfig {
+            dns_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(1),
+            ..OutboundConfig::default()
+        };
+        let addr = SocketAddr::from(([127, 0, 0, 1], 80));
+
+        let resolved = resolve_with(slow_lookup(Duration::from_secs(3), addr), &config).await.unwrap();
+        assert_eq!(resolved, vec![addr]);
+    }
+
+    #[tokio::test]
+    async fn failed_resolution_is_a_resolve_error() {
+        let lookup = async { Err::<Vec<SocketAddr>, _>(io::Error::new(io::ErrorKind::NotFound, "no such host")) };
+        let result = resolve_with(lookup, &OutboundConfig::default()).await;
+        assert!(matches!(result, Err(ConnectError::Resolve(_))), "{result:?}");
+        assert!(result.unwrap_err().to_string().starts_with("域名解析失败"));
+    }
+
+    fn write_socket_rules(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("ua4f-socket-rules-{}-{}.txt", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn socket_rules_override_nodelay_and_keepalive_per_target() {
+        let path = write_socket_rules("tune", "# 大文件镜像关闭 nodelay\n*.MIRROR.example nodelay=off keepalive=30\n\n10.* keepalive=0\n");
+        let config = OutboundConfig { socket_rules: load_socket_rules(&path).unwrap(), ..OutboundConfig::default() };
+        std::fs::remove_file(&path).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tuned = |host: &'static str| {
+            let config = &config;
+            async move {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                config.tune_stream(&stream, host).unwrap();
+                stream
+            }
+        };
+
+        let bulk = tuned("dl.mirror.example").await;
+        assert!(!bulk.nodelay().unwrap());
+        assert!(socket2::SockRef::from(&bulk).keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        assert_eq!(socket2::SockRef::from(&bulk).keepalive_time().unwrap(), Duration::from_secs(30));
+
+        // 未匹配任何规则时沿用默认值
+        let other = tuned("www.example.com").await;
+        assert!(other.nodelay().unwrap());
+        assert!(!socket2::SockRef::from(&other).keepalive().unwrap());
+
+        // 规则只覆盖指定的项
+        let internal = tuned("10.0.0.8").await;
+        assert!(internal.nodelay().unwrap());
+        assert!(!socket2::SockRef::from(&internal).keepalive().unwrap());
+    }
+
+    #[test]
+    fn invalid_socket_rules_report_the_line() {
+        let path = write_socket_rules("invalid", "*.example nodelay=off\nbulk.example nodelay=maybe\n");
+        let err = load_socket_rules(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("第 2 行"), "{err}");
+    }
+}
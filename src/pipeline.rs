@@ -0,0 +1,371 @@
+use bytes::BytesMut;
+use ua4f::http::{self, HttpMethods};
+
+/// 分块编码的块大小行、trailer 行的最大长度，超出视为无法解析
+const MAX_LINE_LEN: usize = 4096;
+
+/// 请求头之后的 body 如何界定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// 没有 body，下一个字节即下一个请求
+    Empty,
+    Length(u64),
+    Chunked,
+    /// 无法确定边界（切换协议、未知的传输编码等），之后的数据原样转发
+    Unknown,
+}
+
+fn body_framing(head: &[u8], methods: &HttpMethods) -> Framing {
+    if http::is_connect_request(head, methods) {
+        return Framing::Unknown;
+    }
+    let mut length = None;
+    let mut chunked = false;
+    for line in head.split(|&c| c == b'\n').skip(1) {
+        let Some(colon) = memchr::memchr(b':', line) else {
+            continue;
+        };
+        let (name, value) = (&line[..colon], line[colon + 1..].trim_ascii());
+        if name.eq_ignore_ascii_case(b"Transfer-Encoding") {
+            // 只有最后一个编码为 chunked 时才能确定请求 body 的边界
+            let last = value.rsplit(|&c| c == b',').next().unwrap_or_default();
+            if !last.trim_ascii().eq_ignore_ascii_case(b"chunked") {
+                return Framing::Unknown;
+            }
+            chunked = true;
+        } else if name.eq_ignore_ascii_case(b"Content-Length") {
+            let parsed = std::str::from_utf8(value).ok().and_then(|value| value.parse::<u64>().ok());
+            match (length, parsed) {
+                (_, None) => return Framing::Unknown,
+                (Some(previous), Some(parsed)) if previous != parsed => return Framing::Unknown,
+                (_, parsed) => length = parsed,
+            }
+        } else if name.eq_ignore_ascii_case(b"Upgrade") {
+            return Framing::Unknown;
+        }
+    }
+    match (chunked, length) {
+        (true, _) => Framing::Chunked,
+        (false, Some(length)) if length > 0 => Framing::Length(length),
+        (false, _) => Framing::Empty,
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    /// 正在累积下一个请求的请求头
+    Head(BytesMut),
+    /// Content-Length 界定的 body 剩余字节数
+    Body(u64),
+    /// 正在读取分块编码的块大小行
+    ChunkSize(Vec<u8>),
+    /// 当前块剩余的字节数（含块数据后的 CRLF）
+    ChunkData(u64),
+    /// 正在读取最后一个块之后的 trailer 行
+    Trailers(Vec<u8>),
+    /// 无法继续跟踪请求边界，之后的数据原样转发
+    Passthrough,
+}
+
+/// 按 Content-Length/chunked 跟踪客户端发往目标的数据中各个请求的边界，
+/// 使同一 keep-alive 连接上的后续请求头也能被改写
+#[derive(Debug)]
+pub struct RequestFramer {
+    state: State,
+    methods: &'static HttpMethods,
+    max_header_size: usize,
+    /// 每次 feed 最多处理的请求头数
+    max_batch: usize,
+    /// 连接上已识别的请求数，含第一个请求
+    requests: u64,
+}
+
+impl RequestFramer {
+    /// `head` 为连接上第一个请求的完整请求头，其后的数据从该请求的 body 开始；
+    /// `max_batch` 为每次 feed 最多处理的请求头数，至少为 1
+    pub fn after_head(head: &[u8], methods: &'static HttpMethods, max_header_size: usize, max_batch: usize) -> Self {
+        RequestFramer {
+            state: state_after(body_framing(head, methods)),
+            methods,
+            max_header_size,
+            max_batch: max_batch.max(1),
+            requests: 1,
+        }
+    }
+
+    /// 连接上已识别的请求数，第一个请求为 1
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// 处理客户端发来的一段数据，应写入目标的数据追加到 `out`，返回已处理的字节数；
+    /// 每个完整的后续请求头在写出前连同其序号（第一个请求为 1）交给 `on_head` 改写。
+    /// 请求头未收全时暂存，待后续数据到达后一并写出。一次最多处理 `max_batch` 个请求头，
+    /// 调用方应先将 `out` 写入目标再处理剩余数据，目标接收不及时便不再读取客户端
+    pub fn feed(&mut self, data: &[u8], out: &mut BytesMut, on_head: &mut impl FnMut(&mut BytesMut, u64)) -> usize {
+        let mut rest = data;
+        let mut heads = 0;
+        while !rest.is_empty() {
+            match &mut self.state {
+                State::Passthrough => {
+                    out.extend_from_slice(rest);
+                    return data.len();
+                }
+                State::Body(remaining) | State::ChunkData(remaining) => {
+                    let n = (*remaining).min(rest.len() as u64) as usize;
+                    out.extend_from_slice(&rest[..n]);
+                    rest = &rest[n..];
+                    *remaining -= n as u64;
+                    if *remaining == 0 {
+                        self.state = match self.state {
+                            State::Body(_) => State::Head(BytesMut::new()),
+                            _ => State::ChunkSize(Vec::new()),
+                        };
+                    }
+                }
+                State::ChunkSize(line) | State::Trailers(line) => {
+                    let (take, complete) = match memchr::memchr(b'\n', rest) {
+                        Some(pos) => (pos + 1, true),
+                        None => (rest.len(), false),
+                    };
+                    line.extend_from_slice(&rest[..take]);
+                    out.extend_from_slice(&rest[..take]);
+                    rest = &rest[take..];
+                    if line.len() > MAX_LINE_LEN {
+                        self.state = State::Passthrough;
+                    } else if complete {
+                        self.state = match &self.state {
+                            State::ChunkSize(line) => match parse_chunk_size(line) {
+                                Some(0) => State::Trailers(Vec::new()),
+                                Some(size) => State::ChunkData(size.saturating_add(2)),
+                                None => State::Passthrough,
+                            },
+                            // trailer 以空行结束，之后是下一个请求
+                            State::Trailers(line) if line.trim_ascii().is_empty() => State::Head(BytesMut::new()),
+                            _ => State::Trailers(Vec::new()),
+                        };
+                    }
+                }
+                State::Head(head) => {
+                    if heads == self.max_batch {
+                        break;
+                    }
+                    let buffered = head.len();
+                    let searched = buffered.saturating_sub(3);
+                    // 最多暂存 max_header_size + 1 字节，请求头结束之后的数据留在 rest 中继续处理
+                    let take = rest.len().min(self.max_header_size + 1 - buffered);
+                    head.extend_from_slice(&rest[..take]);
+                    let Some(pos) = memchr::memmem::find(&head[searched..], b"\r\n\r\n") else {
+                        rest = &rest[take..];
+                        // 不像 HTTP 请求或请求头过长时放弃跟踪，避免无限期暂存数据
+                        if !self.methods.may_start_request(head) || head.len() > self.max_header_size {
+                            out.extend_from_slice(head);
+                            self.state = State::Passthrough;
+                        }
+                        continue;
+                    };
+                    let head_end = searched + pos + 4;
+                    rest = &rest[head_end - buffered..];
+                    head.truncate(head_end);
+                    let mut head = std::mem::take(head);
+                    if !http::is_http_request(&head, self.methods) {
+                        out.extend_from_slice(&head);
+                        self.state = State::Passthrough;
+                        continue;
+                    }
+                    heads += 1;
+                    let framing = body_framing(&head, self.methods);
+                    self.requests += 1;
+                    if http::has_valid_request_line(&head) {
+                        on_head(&mut head, self.requests);
+                    }
+                    out.extend_from_slice(&head);
+                    self.state = state_after(framing);
+                }
+            }
+        }
+        data.len() - rest.len()
+    }
+}
+
+impl RequestFramer {
+    /// 客户端关闭时将暂存的不完整请求头原样追加到 `out`
+    pub fn flush(&mut self, out: &mut BytesMut) {
+        if let State::Head(head) = &mut self.state {
+            out.extend_from_slice(head);
+            head.clear();
+        }
+    }
+}
+
+/// 请求头之后开始读取 body（或下一个请求）时的状态
+fn state_after(framing: Framing) -> State {
+    match framing {
+        Framing::Empty => State::Head(BytesMut::new()),
+        Framing::Length(length) => State::Body(length),
+        Framing::Chunked => State::ChunkSize(Vec::new()),
+        Framing::Unknown => State::Passthrough,
+    }
+}
+
+/// 解析块大小行（十六进制，可带 `;` 之后的扩展）
+fn parse_chunk_size(line: &[u8]) -> Option<u64> {
+    let size = line.split(|&c| c == b';').next().unwrap_or_default().trim_ascii();
+    u64::from_str_radix(std::str::from_utf8(size).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn methods() -> &'static HttpMethods {
+        Box::leak(Box::new(HttpMethods::default()))
+    }
+
+    /// 以 `first` 为第一个请求头创建 framer，按 `chunks` 分段喂入其后的数据，
+    /// 返回写出的数据与 on_head 收到的（序号, 请求行）
+    fn feed_chunks(first: &[u8], chunks: &[&[u8]]) -> (RequestFramer, Vec<u8>, Vec<(u64, String)>) {
+        let mut framer = RequestFramer::after_head(first, methods(), 16 * 1024, usize::MAX);
+        let mut out = BytesMut::new();
+        let mut heads = Vec::new();
+        for chunk in chunks {
+            let consumed = framer.feed(chunk, &mut out, &mut |head, request| {
+                let line = head[..].split(|&c| c == b'\r').next().unwrap_or_default();
+                heads.push((request, String::from_utf8_lossy(line).into_owned()));
+            });
+            assert_eq!(consumed, chunk.len());
+        }
+        framer.flush(&mut out);
+        (framer, out.to_vec(), heads)
+    }
+
+    #[test]
+    fn pipelined_requests_are_numbered_from_one() {
+        let first = b"GET /1 HTTP/1.1\r\nHost: a\r\n\r\n";
+        let rest = b"GET /2 HTTP/1.1\r\nHost: a\r\n\r\nGET /3 HTTP/1.1\r\nHost: a\r\n\r\n";
+        let (framer, out, heads) = feed_chunks(first, &[rest]);
+        assert_eq!(out, rest);
+        assert_eq!(heads, [(2, "GET /2 HTTP/1.1".to_string()), (3, "GET /3 HTTP/1.1".to_string())]);
+        assert_eq!(framer.requests(), 3);
+    }
+
+    #[test]
+    fn burst_is_processed_in_bounded_batches() {
+        const BATCH: usize = 8;
+        const REQUESTS: usize = 1000;
+        let first = b"GET /0 HTTP/1.1\r\nHost: a\r\n\r\n";
+        let mut burst = Vec::new();
+        for i in 1..=REQUESTS {
+            burst.extend_from_slice(format!("POST /{i} HTTP/1.1\r\nContent-Length: 2\r\nUser-Agent: x\r\n\r\nok").as_bytes());
+        }
+        let request_len = burst.len() / REQUESTS + 1;
+
+        let mut framer = RequestFramer::after_head(first, methods(), 16 * 1024, BATCH);
+        let mut forwarded = Vec::new();
+        let mut rest = &burst[..];
+        let mut batches = 0;
+        while !rest.is_empty() {
+            let mut out = BytesMut::new();
+            let consumed = framer.feed(rest, &mut out, &mut |head, _| {
+                // 每个请求头都变长，写出的数据仍受批大小约束
+                head.truncate(head.len() - 2);
+                head.extend_from_slice(b"X-Added: 1\r\n\r\n");
+            });
+            assert!(consumed > 0);
+            assert!(out.len() <= BATCH * (request_len + 16), "单批写出 {} 字节", out.len());
+            forwarded.extend_from_slice(&out);
+            rest = &rest[consumed..];
+            batches += 1;
+        }
+
+        assert_eq!(framer.requests(), REQUESTS as u64 + 1);
+        assert_eq!(batches, REQUESTS.div_ceil(BATCH));
+        assert_eq!(forwarded.len(), burst.len() + REQUESTS * b"X-Added: 1\r\n".len());
+        assert!(forwarded.ends_with(b"POST /1000 HTTP/1.1\r\nContent-Length: 2\r\nUser-Agent: x\r\nX-Added: 1\r\n\r\nok"));
+    }
+
+    /// 在 `rest` 的每个位置切成两段喂入，结果都应原样写出 `rest` 并识别出同样的后续请求
+    fn assert_every_split(first: &[u8], rest: &[u8], expected: &[(u64, &str)]) {
+        for split in 0..=rest.len() {
+            let (_, out, heads) = feed_chunks(first, &[&rest[..split], &rest[split..]]);
+            assert_eq!(out, rest, "在第 {split} 字节处切分");
+            let heads: Vec<(u64, &str)> = heads.iter().map(|(request, line)| (*request, line.as_str())).collect();
+            assert_eq!(heads, expected, "在第 {split} 字节处切分");
+        }
+    }
+
+    #[test]
+    fn content_length_body_is_skipped_at_any_split() {
+        // body 恰好形如一个请求头，不应被当作下一个请求
+        let body = b"GET /smuggled HTTP/1.1\r\n\r\n";
+        let first = format!("POST /1 HTTP/1.1\r\nContent-Length: {}\r\n\r\n", body.len());
+        let mut rest = body.to_vec();
+        rest.extend_from_slice(b"GET /2 HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert_every_split(first.as_bytes(), &rest, &[(2, "GET /2 HTTP/1.1")]);
+    }
+
+    #[test]
+    fn chunked_body_is_skipped_at_any_split() {
+        let first = b"POST /1 HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n";
+        let rest = b"5;name=value\r\nhello\r\n\
+            1a\r\nGET /smuggled HTTP/1.1\r\n\r\n\r\n\
+            0\r\nX-Trailer: GET /t HTTP/1.1\r\n\r\n\
+            GET /2 HTTP/1.1\r\nHost: a\r\n\r\n";
+        assert_every_split(first, rest, &[(2, "GET /2 HTTP/1.1")]);
+    }
+
+    #[test]
+    fn chunked_body_without_trailers_ends_at_the_empty_line() {
+        let first = b"POST /1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let rest = b"3\r\nabc\r\n0\r\n\r\nPOST /2 HTTP/1.1\r\nContent-Length: 2\r\n\r\nokGET /3 HTTP/1.1\r\n\r\n";
+        assert_every_split(first, rest, &[(2, "POST /2 HTTP/1.1"), (3, "GET /3 HTTP/1.1")]);
+    }
+
+    #[test]
+    fn ambiguous_framing_stops_tracking() {
+        // Content-Length 冲突或最后的传输编码不是 chunked 时无法确定 body 边界，之后的数据原样转发
+        for first in [
+            &b"POST /1 HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\n"[..],
+            b"POST /1 HTTP/1.1\r\nContent-Length: x\r\n\r\n",
+            b"POST /1 HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+            b"GET /1 HTTP/1.1\r\nUpgrade: websocket\r\n\r\n",
+        ] {
+            let rest = b"abcdGET /2 HTTP/1.1\r\n\r\n";
+            let (framer, out, heads) = feed_chunks(first, &[rest]);
+            assert_eq!(out, rest, "{}", String::from_utf8_lossy(first));
+            assert!(heads.is_empty());
+            assert_eq!(framer.requests(), 1);
+        }
+    }
+
+    #[test]
+    fn invalid_chunk_size_stops_tracking() {
+        let first = b"POST /1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let rest = b"zz\r\nGET /2 HTTP/1.1\r\n\r\n";
+        let (_, out, heads) = feed_chunks(first, &[rest]);
+        assert_eq!(out, rest);
+        assert!(heads.is_empty());
+    }
+
+    #[test]
+    fn oversized_head_is_forwarded_unchanged() {
+        let first = b"GET /1 HTTP/1.1\r\n\r\n";
+        let mut rest = b"GET /2 HTTP/1.1\r\nX-Padding: ".to_vec();
+        rest.resize(20 * 1024, b'a');
+        rest.extend_from_slice(b"\r\n\r\nGET /3 HTTP/1.1\r\n\r\n");
+        let chunks: Vec<&[u8]> = rest.chunks(1000).collect();
+        let (_, out, heads) = feed_chunks(first, &chunks);
+        assert_eq!(out, rest);
+        assert!(heads.is_empty());
+    }
+
+    #[test]
+    fn incomplete_head_is_flushed_unchanged() {
+        let first = b"GET /1 HTTP/1.1\r\n\r\n";
+        let rest = b"GET /2 HTTP/1.1\r\nUser-Agent: x";
+        let (framer, out, heads) = feed_chunks(first, &[rest]);
+        assert_eq!(out, rest);
+        assert!(heads.is_empty());
+        assert_eq!(framer.requests(), 1);
+    }
+}
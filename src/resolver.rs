@@ -0,0 +1,31 @@
+use std::net::SocketAddr;
+use once_cell::sync::OnceCell;
+use socks5_server::proto::Address;
+
+static TARGET_RESOLVER: OnceCell<Box<dyn TargetResolver>> = OnceCell::new();
+
+/// 连接目标前改写目标地址的扩展点，例如把所有 80 端口的流量重定向到内部缓存。
+/// `client` 为客户端地址
+pub trait TargetResolver: Send + Sync {
+    fn resolve(&self, addr: &Address, client: SocketAddr) -> Address;
+}
+
+/// 默认实现：原样返回客户端请求的地址
+#[derive(Debug, Default)]
+pub struct IdentityResolver;
+
+impl TargetResolver for IdentityResolver {
+    fn resolve(&self, addr: &Address, _client: SocketAddr) -> Address {
+        addr.clone()
+    }
+}
+
+/// 安装自定义的目标地址解析器，需在处理第一个连接之前调用；已安装（或已按默认值初始化）时返回传入的解析器
+pub fn set_target_resolver(resolver: Box<dyn TargetResolver>) -> Result<(), Box<dyn TargetResolver>> {
+    TARGET_RESOLVER.set(resolver)
+}
+
+/// 当前生效的目标地址解析器，未安装时为 IdentityResolver
+pub fn target_resolver() -> &'static dyn TargetResolver {
+    TARGET_RESOLVER.get_or_init(|| Box::new(IdentityResolver)).as_ref()
+}
@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use ua4f::http::RewriteOutcome;
+
+/// 当前活跃（已接受且尚未结束）的连接数
+pub static ACTIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// 启动以来接受的连接总数
+pub static TOTAL_CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// 因 SOCKS5 协议错误而失败的握手次数
+pub static MALFORMED_HANDSHAKES: AtomicU64 = AtomicU64::new(0);
+
+/// 经过改写引擎的请求头累计字节数（按改写后的长度计）
+pub static REWRITTEN_HEADER_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// 改写前后请求头长度差的累计值（改写后减改写前，删除 UA 时为负）
+pub static REWRITE_DELTA_BYTES: AtomicI64 = AtomicI64::new(0);
+
+/// User-Agent 被替换或删除的请求数
+pub static UA_REWRITES: AtomicU64 = AtomicU64::new(0);
+
+/// --dry-run 下本应改写或删除 User-Agent 的请求数
+pub static DRY_RUN_REWRITES: AtomicU64 = AtomicU64::new(0);
+
+/// User-Agent 命中白名单而未改写的请求数
+pub static WHITELIST_HITS: AtomicU64 = AtomicU64::new(0);
+
+/// 所有已结束连接累计转发的字节数：客户端到目标（上行）与目标到客户端（下行）
+pub static TOTAL_UP: AtomicU64 = AtomicU64::new(0);
+pub static TOTAL_DOWN: AtomicU64 = AtomicU64::new(0);
+
+/// 单个监听地址的连接计数
+#[derive(Debug)]
+pub struct ListenerStats {
+    /// 监听地址，作为指标的 listener 标签
+    pub name: String,
+    active: AtomicUsize,
+    total: AtomicU64,
+}
+
+impl ListenerStats {
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn total_connections(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+/// 按启动时绑定的顺序登记的监听地址
+static LISTENERS: Mutex<Vec<Arc<ListenerStats>>> = Mutex::new(Vec::new());
+
+/// 登记一个监听地址，返回其连接计数
+pub fn register_listener(name: String) -> Arc<ListenerStats> {
+    let listener = Arc::new(ListenerStats { name, active: AtomicUsize::new(0), total: AtomicU64::new(0) });
+    LISTENERS.lock().unwrap().push(Arc::clone(&listener));
+    listener
+}
+
+pub fn listeners() -> Vec<Arc<ListenerStats>> {
+    LISTENERS.lock().unwrap().clone()
+}
+
+/// 活跃连接计数守卫：创建时全局与所属监听地址的计数加一，drop 时减一
+pub struct ConnectionGuard(Arc<ListenerStats>);
+
+impl ConnectionGuard {
+    pub fn track(listener: Arc<ListenerStats>) -> Self {
+        ACTIVE_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        TOTAL_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        listener.active.fetch_add(1, Ordering::Relaxed);
+        listener.total.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(listener)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        ACTIVE_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub fn active_connections() -> usize {
+    ACTIVE_CONNECTIONS.load(Ordering::Relaxed)
+}
+
+pub fn total_connections() -> u64 {
+    TOTAL_CONNECTIONS.load(Ordering::Relaxed)
+}
+
+pub fn record_rewrite(header_bytes: usize, delta: i64) {
+    REWRITTEN_HEADER_BYTES.fetch_add(header_bytes as u64, Ordering::Relaxed);
+    REWRITE_DELTA_BYTES.fetch_add(delta, Ordering::Relaxed);
+}
+
+pub fn record_transfer(up: u64, down: u64) {
+    TOTAL_UP.fetch_add(up, Ordering::Relaxed);
+    TOTAL_DOWN.fetch_add(down, Ordering::Relaxed);
+}
+
+/// 按 User-Agent 改写的结果计数
+pub fn record_rewrite_outcome(outcome: RewriteOutcome) {
+    let counter = match outcome {
+        RewriteOutcome::Rewritten => &UA_REWRITES,
+        RewriteOutcome::DryRun => &DRY_RUN_REWRITES,
+        RewriteOutcome::Whitelisted => &WHITELIST_HITS,
+        RewriteOutcome::Unchanged | RewriteOutcome::Missing => return,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_malformed_handshake() {
+    MALFORMED_HANDSHAKES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn malformed_handshakes() -> u64 {
+    MALFORMED_HANDSHAKES.load(Ordering::Relaxed)
+}
+
+/// 非 HTTP 缓存条目失效前被复用次数的分桶上界，最后一个桶统计超过 16 次的条目
+const CACHE_REUSE_BOUNDS: [u64; 4] = [0, 1, 4, 16];
+const CACHE_REUSE_LABELS: [&str; 5] = ["0", "1", "2-4", "5-16", ">16"];
+
+/// 已失效（过期或被淘汰）的非 HTTP 缓存条目的复用次数分布
+static CACHE_REUSE_BUCKETS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+
+pub fn record_cache_reuse(reuses: u64) {
+    let bucket = CACHE_REUSE_BOUNDS
+        .iter()
+        .position(|&bound| reuses <= bound)
+        .unwrap_or(CACHE_REUSE_BOUNDS.len());
+    CACHE_REUSE_BUCKETS[bucket].fetch_add(1, Ordering::Relaxed);
+}
+
+/// 形如 `0:12 1:3 2-4:5 5-16:0 >16:1` 的复用次数分布
+pub fn cache_reuse_summary() -> String {
+    CACHE_REUSE_LABELS
+        .iter()
+        .zip(&CACHE_REUSE_BUCKETS)
+        .map(|(label, count)| format!("{}:{}", label, count.load(Ordering::Relaxed)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
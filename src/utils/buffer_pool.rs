@@ -0,0 +1,66 @@
+use bytes::BytesMut;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// 固定大小缓冲区的复用池，归还的缓冲区最多保留 `max_idle` 个，超出部分直接释放
+#[derive(Debug)]
+pub struct BufferPool {
+    buffer_size: usize,
+    max_idle: usize,
+    idle: Mutex<Vec<BytesMut>>,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize, max_idle: usize) -> Self {
+        BufferPool {
+            buffer_size,
+            max_idle,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 取出一个长度为 `buffer_size` 的缓冲区，池为空时新分配；drop 时自动归还。
+    /// 复用的缓冲区保留上次的内容，调用方只应读取自己写入的部分
+    pub fn get(&self) -> PooledBuffer<'_> {
+        let buf = self.idle.lock().unwrap().pop().unwrap_or_else(|| {
+            let mut buf = BytesMut::with_capacity(self.buffer_size);
+            buf.resize(self.buffer_size, 0);
+            buf
+        });
+        PooledBuffer { pool: self, buf }
+    }
+}
+
+/// 从 `BufferPool` 取出的缓冲区
+#[derive(Debug)]
+pub struct PooledBuffer<'a> {
+    pool: &'a BufferPool,
+    buf: BytesMut,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &BytesMut {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        // 长度被调用方改变的缓冲区不再放回，保证取出的缓冲区长度一致
+        if self.buf.len() != self.pool.buffer_size {
+            return;
+        }
+        let mut idle = self.pool.idle.lock().unwrap();
+        if idle.len() < self.pool.max_idle {
+            idle.push(std::mem::take(&mut self.buf));
+        }
+    }
+}
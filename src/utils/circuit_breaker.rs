@@ -0,0 +1,155 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// 正常放行，记录连续失败次数
+    Closed,
+    /// 冷却中，拒绝所有请求
+    Open,
+    /// 冷却结束，只放行一个探测请求
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum Inner {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// `since` 为放行探测请求的时间；探测请求被取消而没有回报结果时，再过一个冷却期重新放行
+    HalfOpen { since: Instant },
+}
+
+/// 连续失败 `threshold` 次后打开，冷却 `cooldown` 后半开放行一个探测请求，
+/// 探测成功则关闭，失败则重新打开
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    /// `threshold` 至少为 1
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner::Closed { failures: 0 }),
+        }
+    }
+
+    /// 是否放行本次请求；放行后必须以 `record` 回报结果。
+    /// 状态因此改变（打开转为半开）时一并返回新状态
+    pub fn try_acquire(&self) -> (bool, Option<BreakerState>) {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        match *inner {
+            Inner::Closed { .. } => (true, None),
+            Inner::Open { until } if now >= until => {
+                *inner = Inner::HalfOpen { since: now };
+                (true, Some(BreakerState::HalfOpen))
+            }
+            Inner::HalfOpen { since } if now >= since + self.cooldown => {
+                *inner = Inner::HalfOpen { since: now };
+                (true, None)
+            }
+            Inner::Open { .. } | Inner::HalfOpen { .. } => (false, None),
+        }
+    }
+
+    /// 回报放行请求的结果，状态因此改变时返回新状态
+    pub fn record(&self, success: bool) -> Option<BreakerState> {
+        let mut inner = self.inner.lock().unwrap();
+        let open = Inner::Open { until: Instant::now() + self.cooldown };
+        match (&mut *inner, success) {
+            (Inner::Closed { failures }, true) => {
+                *failures = 0;
+                None
+            }
+            (Inner::Closed { failures }, false) => {
+                *failures += 1;
+                if *failures < self.threshold {
+                    return None;
+                }
+                *inner = open;
+                Some(BreakerState::Open)
+            }
+            // 打开期间不会放行请求，这里只可能是打开前已放行、较晚结束的请求，不影响状态
+            (Inner::Open { .. }, _) => None,
+            (Inner::HalfOpen { .. }, true) => {
+                *inner = Inner::Closed { failures: 0 };
+                Some(BreakerState::Closed)
+            }
+            (Inner::HalfOpen { .. }, false) => {
+                *inner = open;
+                Some(BreakerState::Open)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_open_half_open_closed() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+
+        // 连续失败未达阈值时保持关闭，成功会清零计数
+        assert_eq!(breaker.try_acquire(), (true, None));
+        assert_eq!(breaker.record(false), None);
+        assert_eq!(breaker.record(false), None);
+        assert_eq!(breaker.record(true), None);
+        assert_eq!(breaker.record(false), None);
+        assert_eq!(breaker.record(false), None);
+        assert_eq!(breaker.record(false), Some(BreakerState::Open));
+
+        // 冷却期内拒绝
+        assert_eq!(breaker.try_acquire(), (false, None));
+        tokio::time::advance(COOLDOWN - Duration::from_secs(1)).await;
+        assert_eq!(breaker.try_acquire(), (false, None));
+
+        // 冷却结束后只放行一个探测请求
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(breaker.try_acquire(), (true, Some(BreakerState::HalfOpen)));
+        assert_eq!(breaker.try_acquire(), (false, None));
+
+        // 探测成功后关闭
+        assert_eq!(breaker.record(true), Some(BreakerState::Closed));
+        assert_eq!(breaker.try_acquire(), (true, None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_probe_reopens() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        assert_eq!(breaker.record(false), Some(BreakerState::Open));
+        tokio::time::advance(COOLDOWN).await;
+        assert_eq!(breaker.try_acquire(), (true, Some(BreakerState::HalfOpen)));
+        assert_eq!(breaker.record(false), Some(BreakerState::Open));
+        assert_eq!(breaker.try_acquire(), (false, None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abandoned_probe_is_retried_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        breaker.record(false);
+        tokio::time::advance(COOLDOWN).await;
+        assert_eq!(breaker.try_acquire(), (true, Some(BreakerState::HalfOpen)));
+        // 探测请求被取消、没有回报结果：再过一个冷却期重新放行
+        tokio::time::advance(COOLDOWN).await;
+        assert_eq!(breaker.try_acquire(), (true, None));
+    }
+
+    #[test]
+    fn late_result_while_open_is_ignored() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        breaker.record(false);
+        assert_eq!(breaker.record(true), None);
+        assert_eq!(breaker.try_acquire(), (false, None));
+    }
+}
@@ -0,0 +1,99 @@
+use memchr::memmem;
+
+/// 客户端到目标方向的内容过滤：出现任一字节模式即拆除连接；
+/// 每个连接只扫描前 `scan_limit` 字节以限制开销
+#[derive(Debug)]
+pub struct ContentFilter {
+    patterns: Vec<Box<[u8]>>,
+    scan_limit: usize,
+    max_pattern_len: usize,
+}
+
+impl ContentFilter {
+    /// 忽略空模式；没有非空模式时返回 None
+    pub fn new(patterns: &[String], scan_limit: usize) -> Option<Self> {
+        let patterns: Vec<Box<[u8]>> = patterns
+            .iter()
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| Box::from(pattern.as_bytes()))
+            .collect();
+        let max_pattern_len = patterns.iter().map(|pattern| pattern.len()).max()?;
+        Some(ContentFilter { patterns, scan_limit, max_pattern_len })
+    }
+
+    pub fn scanner(&self) -> ContentScanner<'_> {
+        ContentScanner { filter: self, scanned: 0, window: Vec::new() }
+    }
+}
+
+/// 单个连接的扫描状态，保留上一段末尾的字节以匹配跨越读取边界的模式
+pub struct ContentScanner<'a> {
+    filter: &'a ContentFilter,
+    scanned: usize,
+    window: Vec<u8>,
+}
+
+impl<'a> ContentScanner<'a> {
+    /// 扫描新到达的数据，返回命中的模式；累计扫描量达到上限后不再扫描
+    pub fn feed(&mut self, data: &[u8]) -> Option<&'a [u8]> {
+        let remaining = self.filter.scan_limit.saturating_sub(self.scanned);
+        if remaining == 0 {
+            return None;
+        }
+        let data = &data[..data.len().min(remaining)];
+        self.scanned += data.len();
+        self.window.extend_from_slice(data);
+
+        if let Some(pattern) = self.filter.patterns.iter().find(|pattern| memmem::find(&self.window, pattern).is_some()) {
+            return Some(pattern);
+        }
+        let keep = self.filter.max_pattern_len - 1;
+        if self.window.len() > keep {
+            self.window.drain(..self.window.len() - keep);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContentFilter;
+
+    fn filter(patterns: &[&str], scan_limit: usize) -> ContentFilter {
+        let patterns: Vec<String> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        ContentFilter::new(&patterns, scan_limit).unwrap()
+    }
+
+    #[test]
+    fn pattern_within_one_chunk() {
+        let filter = filter(&["EVIL", "BAD"], 1024);
+        let mut scanner = filter.scanner();
+        assert_eq!(scanner.feed(b"all good"), None);
+        assert_eq!(scanner.feed(b"xx BAD xx"), Some(&b"BAD"[..]));
+    }
+
+    #[test]
+    fn pattern_split_across_chunks() {
+        let filter = filter(&["EVIL"], 1024);
+        let mut scanner = filter.scanner();
+        assert_eq!(scanner.feed(b"....EV"), None);
+        assert_eq!(scanner.feed(b"I"), None);
+        assert_eq!(scanner.feed(b"L...."), Some(&b"EVIL"[..]));
+    }
+
+    #[test]
+    fn bytes_beyond_the_scan_limit_are_not_checked() {
+        let filter = filter(&["EVIL"], 8);
+        let mut scanner = filter.scanner();
+        assert_eq!(scanner.feed(b"12345EV"), None);
+        // 只有前 8 字节参与扫描，`EVI` 不完整
+        assert_eq!(scanner.feed(b"IL"), None);
+        assert_eq!(scanner.feed(b"EVIL"), None);
+    }
+
+    #[test]
+    fn empty_patterns_disable_the_filter() {
+        assert!(ContentFilter::new(&[], 1024).is_none());
+        assert!(ContentFilter::new(&[String::new()], 1024).is_none());
+    }
+}
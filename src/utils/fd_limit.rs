@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 当前进程可打开文件描述符数的软限制；无限制或无法获取时返回 None
+#[cfg(target_os = "linux")]
+pub fn soft_nofile_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit 只写入传入的 rlimit 结构体
+    let ret = unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) };
+    if ret != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+    // 部分 32 位目标上 rlim_t 不是 u64
+    #[allow(clippy::unnecessary_cast)]
+    Some(limit.rlim_cur as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn soft_nofile_limit() -> Option<u64> {
+    None
+}
+
+/// 以文件描述符数计的预算：每个连接及其额外打开的套接字各自占用份额，释放 permit 时归还
+#[derive(Debug, Clone)]
+pub struct FdBudget {
+    semaphore: Arc<Semaphore>,
+}
+
+impl FdBudget {
+    pub fn new(limit: u64) -> Self {
+        let permits = usize::try_from(limit).unwrap_or(usize::MAX).min(Semaphore::MAX_PERMITS);
+        FdBudget { semaphore: Arc::new(Semaphore::new(permits)) }
+    }
+
+    /// 预算不足 `fds` 个时返回 None
+    pub fn try_reserve(&self, fds: u32) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.semaphore).try_acquire_many_owned(fds).ok()
+    }
+
+    /// 等待预算中有 `fds` 个空位
+    pub async fn reserve(&self, fds: u32) -> OwnedSemaphorePermit {
+        Arc::clone(&self.semaphore)
+            .acquire_many_owned(fds)
+            .await
+            .expect("文件描述符预算的信号量不会被关闭")
+    }
+
+    /// 剩余可用的文件描述符数
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_once_the_budget_is_exhausted() {
+        let budget = FdBudget::new(5);
+        let first = budget.try_reserve(2).unwrap();
+        let _second = budget.try_reserve(2).unwrap();
+        // 只剩 1 个，不够一个新连接，但够一个额外的 UDP 套接字
+        assert!(budget.try_reserve(2).is_none());
+        let udp = budget.try_reserve(1).unwrap();
+        assert!(budget.try_reserve(1).is_none());
+        assert_eq!(budget.available(), 0);
+
+        drop(first);
+        drop(udp);
+        assert_eq!(budget.available(), 3);
+        assert!(budget.try_reserve(2).is_some());
+    }
+
+    #[tokio::test]
+    async fn reserve_waits_for_released_fds() {
+        let budget = FdBudget::new(2);
+        let held = budget.try_reserve(2).unwrap();
+        let waiting = tokio::spawn({
+            let budget = budget.clone();
+            async move { budget.reserve(2).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        drop(held);
+        let _permit = waiting.await.unwrap();
+    }
+
+    #[test]
+    fn huge_limits_are_clamped() {
+        assert_eq!(FdBudget::new(u64::MAX).available(), Semaphore::MAX_PERMITS);
+    }
+}
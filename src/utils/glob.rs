@@ -0,0 +1,56 @@
+/// 简单的 glob 匹配：`*` 匹配任意长度（含空）字节，`?` 匹配单个字节，其余按字节精确比较
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // 最近一次 `*` 的位置，以及当时对应的 text 位置，用于回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        // 先处理 `*`：否则模式中的 `*` 会按字面匹配文本中的 `*` 而不记录回溯点
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn literal_and_question_mark() {
+        assert!(glob_match(b"/api/v1", b"/api/v1"));
+        assert!(!glob_match(b"/api/v1", b"/api/v2x"));
+        assert!(glob_match(b"/api/v?", b"/api/v2"));
+        assert!(!glob_match(b"/api/v?", b"/api/v"));
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob_match(b"/api/*", b"/api/"));
+        assert!(glob_match(b"/api/*", b"/api/users/1"));
+        assert!(glob_match(b"*.example.com", b"cdn.example.com"));
+        assert!(!glob_match(b"*.example.com", b"example.com"));
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"a*b*c", b"axxbyyc"));
+        assert!(!glob_match(b"a*b*c", b"axxbyy"));
+    }
+
+    #[test]
+    fn star_in_pattern_against_literal_star_in_text() {
+        assert!(glob_match(b"a*", b"a*b"));
+        assert!(glob_match(b"*b", b"*ab"));
+        assert!(glob_match(b"a*c", b"a*bc"));
+    }
+}
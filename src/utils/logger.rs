@@ -1,7 +1,9 @@
 use std::fs::{create_dir_all, OpenOptions, File};
 use std::io::{Write, Result, Seek, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
 use time::macros::format_description;
 use time::UtcOffset;
 use tracing_subscriber::{fmt, EnvFilter, Layer, Registry};
@@ -11,34 +13,153 @@ use tracing_subscriber::fmt::time::OffsetTime;
 #[cfg(target_os = "linux")]
 const LOG_DIR: &str = "/var/log/";
 
-#[cfg(target_os = "windows")]
+#[cfg(not(target_os = "linux"))]
 const LOG_DIR: &str = "./log/";
 
 const LOG_FILE: &str = "ua4f.log";
-/// 日志文件超过 5MB 后进行复写（清空日志）
-const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024; // 5MB
+/// 日志文件持续写入失败时，控制台告警的最小间隔
+const WRITE_FAILURE_WARN_INTERVAL: Duration = Duration::from_secs(60);
 
-/// 自定义文件写入器：在写入前检测文件大小，超过阈值则清空文件
+/// 控制台与文件日志的输出格式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 便于阅读的文本格式
+    #[default]
+    Text,
+    /// 每行一个 JSON 对象，事件与 span 的字段均为独立的键
+    Json,
+}
+
+/// 文件日志的位置与轮转方式
+#[derive(Debug, Clone)]
+pub struct FileLogOptions {
+    /// 日志目录，不存在时自动创建；None 时 Linux 使用 `/var/log/`，其他系统使用 `./log/`
+    pub dir: Option<PathBuf>,
+    /// 日志文件名，None 时为 `ua4f.log`
+    pub file_name: Option<String>,
+    /// 日志文件超过该大小（字节）后轮转
+    pub max_size: u64,
+    /// 保留的历史文件数（`ua4f.log.1` 为最新），0 表示直接清空当前文件
+    pub keep: usize,
+}
+
+fn open_log_file(path: &Path) -> Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `ua4f.log` 的第 `index` 个历史文件 `ua4f.log.<index>`
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// 日志文件及其写入失败状态
+struct LogFile {
+    /// 轮转期间暂时关闭；重新打开失败时为 None，下次写入时重试
+    file: Option<File>,
+    path: PathBuf,
+    keep: usize,
+    /// 连续写入失败次数，写入成功后清零
+    consecutive_failures: u64,
+    last_warned: Option<Instant>,
+}
+
+impl LogFile {
+    fn write_rotating(&mut self, buf: &[u8], max_size: u64) -> Result<usize> {
+        let len = match &self.file {
+            Some(file) => file.metadata()?.len(),
+            None => {
+                self.file = Some(open_log_file(&self.path)?);
+                0
+            }
+        };
+        // 如果当前文件大小加上本次写入内容超过阈值，则轮转文件
+        if len > 0 && len + buf.len() as u64 > max_size {
+            self.rotate()?;
+        }
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => Err(std::io::Error::other("日志文件未打开")),
+        }
+    }
+
+    /// 依次将 `.1`…`.keep-1` 改名为下一个序号、当前文件改名为 `.1`，再创建新文件；超出 keep 的最旧文件被覆盖。
+    /// Windows 上打开的句柄会阻止改名，因此先关闭当前文件
+    fn rotate(&mut self) -> Result<()> {
+        if self.keep == 0 {
+            if let Some(file) = &mut self.file {
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+            }
+            return Ok(());
+        }
+
+        self.file = None;
+        for index in (1..self.keep).rev() {
+            let from = rotated_path(&self.path, index);
+            if from.exists() {
+                // 历史文件改名失败时放弃该文件，不影响后续轮转
+                let _ = std::fs::rename(&from, rotated_path(&self.path, index + 1));
+            }
+        }
+        let renamed = std::fs::rename(&self.path, rotated_path(&self.path, 1));
+        // 改名失败时继续写入原文件，避免日志中断
+        self.file = Some(open_log_file(&self.path)?);
+        renamed
+    }
+
+    /// 写入一条日志并跟踪失败状态：失败时向 `console` 输出节流后的告警，恢复后静默重置失败计数
+    fn write_logged(&mut self, buf: &[u8], max_size: u64, console: &mut impl Write) -> Result<usize> {
+        match self.write_rotating(buf, max_size) {
+            Ok(n) => {
+                self.consecutive_failures = 0;
+                self.last_warned = None;
+                Ok(n)
+            }
+            Err(err) => {
+                self.record_failure(&err, console);
+                Err(err)
+            }
+        }
+    }
+
+    /// tracing 会忽略写入器返回的错误，这里直接向控制台（stdout）输出节流后的告警，
+    /// 避免磁盘写满等情况下文件日志被静默丢弃
+    fn record_failure(&mut self, err: &std::io::Error, console: &mut impl Write) {
+        self.consecutive_failures += 1;
+        let should_warn = self
+            .last_warned
+            .is_none_or(|at| at.elapsed() >= WRITE_FAILURE_WARN_INTERVAL);
+        if should_warn {
+            self.last_warned = Some(Instant::now());
+            let _ = writeln!(
+                console,
+                "[Warning] 写入日志文件失败（连续 {} 次）: {}",
+                self.consecutive_failures, err
+            );
+        }
+    }
+}
+
+/// 自定义文件写入器：在写入前检测文件大小，超过阈值则轮转文件
 struct RotatingFileWriter {
-    file: Arc<Mutex<File>>,
+    file: Arc<Mutex<LogFile>>,
     max_size: u64,
 }
 
 impl Write for RotatingFileWriter {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let mut file = self.file.lock().unwrap();
-        let metadata = file.metadata()?;
-        // 如果当前文件大小加上本次写入内容超过阈值，则清空文件
-        if metadata.len() + buf.len() as u64 > self.max_size {
-            file.set_len(0)?;
-            file.seek(SeekFrom::Start(0))?;
-        }
-        file.write(buf)
+        self.file.lock().unwrap().write_logged(buf, self.max_size, &mut std::io::stdout())
     }
 
     fn flush(&mut self) -> Result<()> {
-        let mut file = self.file.lock().unwrap();
-        file.flush()
+        let mut log_file = self.file.lock().unwrap();
+        match &mut log_file.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
     }
 }
 
@@ -51,7 +172,14 @@ impl Clone for RotatingFileWriter {
     }
 }
 
-pub fn init_logger(level: String, no_file_log: bool) {
+/// `otlp_endpoint` 仅在启用 `otel` feature 时生效
+pub fn init_logger(
+    level: String,
+    format: LogFormat,
+    no_file_log: bool,
+    file_log: FileLogOptions,
+    otlp_endpoint: Option<&str>,
+) {
     let local_offset = UtcOffset::current_local_offset().unwrap_or_else(|_| {
         eprintln!("[Warning] Unable to determine local time offset. Falling back to UTC.");
         UtcOffset::UTC
@@ -62,51 +190,138 @@ pub fn init_logger(level: String, no_file_log: bool) {
         format_description!("[month]-[day] [hour]:[minute]:[second].[subsecond digits:2]"),
     );
 
+    // EnvFilter::new 会静默丢弃无法解析的指令，这里先校验，失败时回退到 info
+    let level = match EnvFilter::try_new(&level) {
+        Ok(_) => level,
+        Err(err) => {
+            eprintln!("[Warning] Invalid log level \"{}\": {}. Falling back to info.", level, err);
+            "info".to_string()
+        }
+    };
+
     // 控制台层
     let console_layer = fmt::Layer::default()
         .with_writer(std::io::stdout)
         .with_timer(timer.clone())
-        .with_ansi(atty::is(atty::Stream::Stdout)) // 仅在交互式终端启用 ANSI 颜色
-        .with_target(true) // 显示目标模块
-        .with_filter(EnvFilter::new(level.clone()));
+        .with_ansi(format == LogFormat::Text && atty::is(atty::Stream::Stdout)) // 仅在交互式终端启用 ANSI 颜色
+        .with_target(true); // 显示目标模块
+    let console_layer = match format {
+        LogFormat::Text => console_layer.boxed(),
+        LogFormat::Json => console_layer.json().boxed(),
+    }
+    .with_filter(EnvFilter::new(level.clone()));
 
     // 单一日志文件层（使用自定义文件写入器实现超过5MB后复写日志文件）
     let file_layer = if !no_file_log {
-        let log_dir = Path::new(LOG_DIR);
+        let log_dir = file_log.dir.as_deref().unwrap_or(Path::new(LOG_DIR));
 
         // 创建日志目录
-        create_dir_all(log_dir).expect("Unable to create log directory");
+        create_dir_all(log_dir)
+            .unwrap_or_else(|err| panic!("Unable to create log directory {}: {}", log_dir.display(), err));
 
         // 打开日志文件（以追加方式打开）
-        let log_file_path = log_dir.join(LOG_FILE);
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_file_path)
-            .expect("Unable to open log file");
+        let log_file_path = log_dir.join(file_log.file_name.as_deref().unwrap_or(LOG_FILE));
+        let file = open_log_file(&log_file_path)
+            .unwrap_or_else(|err| panic!("Unable to open log file {}: {}", log_file_path.display(), err));
 
         // 构造自定义写入器
         let rotating_writer = RotatingFileWriter {
-            file: Arc::new(Mutex::new(file)),
-            max_size: MAX_LOG_SIZE,
+            file: Arc::new(Mutex::new(LogFile {
+                file: Some(file),
+                path: log_file_path,
+                keep: file_log.keep,
+                consecutive_failures: 0,
+                last_warned: None,
+            })),
+            max_size: file_log.max_size,
         };
 
-        Some(fmt::Layer::default()
+        let file_layer = fmt::Layer::default()
             .with_writer(move || rotating_writer.clone())
             .with_timer(timer) // 使用与控制台相同的时间格式
             .with_ansi(false)  // 文件日志不需要颜色
-            .with_target(true)
-            .with_filter(EnvFilter::new(level)))
+            .with_target(true);
+        let file_layer = match format {
+            LogFormat::Text => file_layer.boxed(),
+            LogFormat::Json => file_layer.json().boxed(),
+        };
+        Some(file_layer.with_filter(EnvFilter::new(level)))
     } else {
         None
     };
 
+    // OTLP trace 导出层：只导出本程序的 span，不受控制台日志级别影响
+    #[cfg(feature = "otel")]
+    let otel_layer = otlp_endpoint.and_then(|endpoint| match super::otel::layer(endpoint) {
+        Ok(layer) => Some(layer.with_filter(
+            tracing_subscriber::filter::Targets::new().with_target("ua4f", tracing::Level::DEBUG),
+        )),
+        Err(err) => {
+            eprintln!("[Warning] Unable to initialize OTLP exporter: {}", err);
+            None
+        }
+    });
+    #[cfg(not(feature = "otel"))]
+    let _ = otlp_endpoint;
+
     // 构建订阅者
     let subscriber = Registry::default()
         .with(console_layer)
         .with(file_layer); // 添加文件层（如果启用）
+    #[cfg(feature = "otel")]
+    let subscriber = subscriber.with(otel_layer);
 
     // 设置全局订阅者
     tracing::subscriber::set_global_default(subscriber)
         .expect("Unable to set global tracing subscriber");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_file(path: &Path) -> LogFile {
+        LogFile {
+            file: Some(open_log_file(path).unwrap()),
+            path: path.to_path_buf(),
+            keep: 0,
+            consecutive_failures: 0,
+            last_warned: None,
+        }
+    }
+
+    /// /dev/full 上的写入总是以 ENOSPC 失败，模拟磁盘写满
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn write_failures_warn_once_per_interval_and_recover_quietly() {
+        let mut file = log_file(Path::new("/dev/full"));
+        let mut console = Vec::new();
+        for _ in 0..3 {
+            assert!(file.write_logged(b"line\n", u64::MAX, &mut console).is_err());
+        }
+        let warnings = String::from_utf8(console.clone()).unwrap();
+        assert_eq!(warnings.lines().count(), 1, "{warnings}");
+        assert!(warnings.contains("连续 1 次"));
+
+        // 间隔过后再次失败时重新告警，带上累计的失败次数
+        file.last_warned = Instant::now().checked_sub(WRITE_FAILURE_WARN_INTERVAL);
+        assert!(file.write_logged(b"line\n", u64::MAX, &mut console).is_err());
+        let warnings = String::from_utf8(console.clone()).unwrap();
+        assert_eq!(warnings.lines().count(), 2);
+        assert!(warnings.lines().nth(1).unwrap().contains("连续 4 次"));
+
+        // 写入恢复后不输出任何内容，之后的失败重新从 1 计数并立即告警
+        let recovered = std::env::temp_dir().join(format!("ua4f-logger-test-{}.log", std::process::id()));
+        file.file = Some(open_log_file(&recovered).unwrap());
+        assert!(file.write_logged(b"line\n", u64::MAX, &mut console).is_ok());
+        assert_eq!(String::from_utf8(console.clone()).unwrap().lines().count(), 2);
+        assert_eq!(file.consecutive_failures, 0);
+
+        file.file = Some(open_log_file(Path::new("/dev/full")).unwrap());
+        assert!(file.write_logged(b"line\n", u64::MAX, &mut console).is_err());
+        let warnings = String::from_utf8(console).unwrap();
+        assert_eq!(warnings.lines().count(), 3);
+        assert!(warnings.lines().nth(2).unwrap().contains("连续 1 次"));
+        let _ = std::fs::remove_file(recovered);
+    }
+}
@@ -1 +1,14 @@
-pub mod logger;
\ No newline at end of file
+pub mod buffer_pool;
+pub mod circuit_breaker;
+pub mod content_filter;
+pub mod fd_limit;
+pub mod glob;
+pub mod logger;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod port_range;
+pub mod rate_limit;
+pub mod rule_cache;
+pub mod target_limit;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+pub mod systemd;
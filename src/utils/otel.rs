@@ -0,0 +1,40 @@
+use once_cell::sync::OnceCell;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+// 退出时需通过 provider 导出批量队列中尚未发送的 span
+static PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
+
+/// 将 span 通过 OTLP/HTTP 导出到 `endpoint` 的 tracing 层。
+/// 导出在后台批量进行，收集端不可用时只会丢弃 span，不会阻塞代理流量
+pub fn layer<S>(endpoint: &str) -> Result<impl Layer<S>, String>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("ua4f").build())
+        .build();
+    let tracer = provider.tracer("ua4f");
+    PROVIDER.set(provider).ok();
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// 导出剩余的 span 并关闭导出器，会阻塞直到发送完成或超时；未启用导出时什么也不做
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            eprintln!("[Warning] Failed to flush OTLP spans: {}", err);
+        }
+    }
+}
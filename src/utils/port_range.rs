@@ -0,0 +1,93 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// 闭区间端口范围，写作单个端口 `80` 或范围 `8000-8999`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|_| format!("无效的端口: {:?}", port))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => {
+                let port = parse(s)?;
+                (port, port)
+            }
+        };
+        if start > end {
+            return Err(format!("端口范围起点大于终点: {}", s));
+        }
+        Ok(PortRange { start, end })
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl Serialize for PortRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PortRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PortRange;
+
+    #[test]
+    fn ports_inside_and_outside_a_range() {
+        let range: PortRange = "8000-8999".parse().unwrap();
+        assert!(range.contains(8000));
+        assert!(range.contains(8500));
+        assert!(range.contains(8999));
+        assert!(!range.contains(7999));
+        assert!(!range.contains(9000));
+    }
+
+    #[test]
+    fn single_port_and_display_round_trip() {
+        let port: PortRange = "80".parse().unwrap();
+        assert_eq!(port, PortRange { start: 80, end: 80 });
+        assert!(port.contains(80) && !port.contains(81));
+        assert_eq!(port.to_string(), "80");
+        assert_eq!(" 8000 - 8999 ".trim().parse::<PortRange>().unwrap().to_string(), "8000-8999");
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        assert!("8999-8000".parse::<PortRange>().is_err());
+        assert!("8000-".parse::<PortRange>().is_err());
+        assert!("http".parse::<PortRange>().is_err());
+        assert!("70000".parse::<PortRange>().is_err());
+    }
+}
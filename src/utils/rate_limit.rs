@@ -0,0 +1,82 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 令牌桶：按固定速率补充令牌，最多累积 `burst` 个
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// `rate` 为每秒补充的令牌数，`burst` 为桶容量（至少为 1）
+    pub fn new(rate: u32, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        TokenBucket {
+            rate: f64::from(rate),
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// 取走一个令牌，令牌不足时等待至补充完成
+    pub async fn acquire(&mut self) {
+        self.refill();
+        if self.tokens < 1.0 {
+            let wait = (1.0 - self.tokens) / self.rate;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+            self.refill();
+        }
+        self.tokens = (self.tokens - 1.0).max(0.0);
+    }
+
+    /// 取走一个令牌，令牌不足时不等待，直接返回 false
+    pub fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_a_refill_once_the_burst_is_spent() {
+        let mut bucket = TokenBucket::new(4, 2);
+        let started = Instant::now();
+        bucket.acquire().await;
+        bucket.acquire().await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert!(!bucket.try_acquire());
+
+        bucket.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tokens_accumulate_up_to_the_burst() {
+        let mut bucket = TokenBucket::new(10, 3);
+        for _ in 0..3 {
+            assert!(bucket.try_acquire());
+        }
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        for _ in 0..3 {
+            assert!(bucket.try_acquire());
+        }
+        assert!(!bucket.try_acquire());
+    }
+}
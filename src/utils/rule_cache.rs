@@ -0,0 +1,77 @@
+use moka::sync::Cache;
+
+/// 默认最多缓存的目标主机数，超出后按 LRU 淘汰
+pub const DEFAULT_CAPACITY: u64 = 4096;
+
+/// 按目标主机缓存第一条匹配规则的下标（无匹配为 None），重复连接同一主机时不再逐条进行 glob 匹配；
+/// 缓存的是下标而不是规则内容，规则列表不变时结果始终有效
+#[derive(Debug)]
+pub struct HostRuleCache {
+    /// 容量为 0 时为 None，每次都重新匹配
+    cache: Option<Cache<String, Option<usize>>>,
+}
+
+impl HostRuleCache {
+    pub fn new(capacity: u64) -> Self {
+        HostRuleCache { cache: (capacity > 0).then(|| Cache::new(capacity)) }
+    }
+
+    /// 取 `host`（已转为小写）的匹配结果，未缓存时调用 `find` 逐条匹配并缓存
+    pub fn get_or_find(&self, host: &str, find: impl FnOnce() -> Option<usize>) -> Option<usize> {
+        match &self.cache {
+            Some(cache) => match cache.get(host) {
+                Some(index) => index,
+                None => {
+                    let index = find();
+                    cache.insert(host.to_string(), index);
+                    index
+                }
+            },
+            None => find(),
+        }
+    }
+}
+
+impl Default for HostRuleCache {
+    fn default() -> Self {
+        HostRuleCache::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn repeated_hosts_skip_the_matcher() {
+        let cache = HostRuleCache::default();
+        let calls = Cell::new(0);
+        let find = |index| {
+            calls.set(calls.get() + 1);
+            index
+        };
+        // 未命中时匹配并缓存，包括无匹配规则的结果
+        assert_eq!(cache.get_or_find("a.example", || find(Some(2))), Some(2));
+        assert_eq!(cache.get_or_find("b.example", || find(None)), None);
+        assert_eq!(calls.get(), 2);
+        // 命中时直接返回缓存的结果
+        assert_eq!(cache.get_or_find("a.example", || find(Some(0))), Some(2));
+        assert_eq!(cache.get_or_find("b.example", || find(Some(0))), None);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn zero_capacity_matches_every_time() {
+        let cache = HostRuleCache::new(0);
+        let calls = Cell::new(0);
+        for _ in 0..3 {
+            cache.get_or_find("a.example", || {
+                calls.set(calls.get() + 1);
+                Some(1)
+            });
+        }
+        assert_eq!(calls.get(), 3);
+    }
+}
@@ -0,0 +1,72 @@
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// 向 `$NOTIFY_SOCKET` 发送 sd_notify 状态；未在 systemd（Type=notify）下运行时什么也不做
+pub fn notify(state: &str) -> io::Result<()> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let path = path.into_encoded_bytes();
+    // 以 `@` 开头表示抽象命名空间的套接字
+    let addr = match path.strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(OsStr::from_bytes(&path))?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// systemd 要求的看门狗间隔（`$WATCHDOG_USEC`），未配置或不属于本进程时返回 None
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_sends_the_state_to_the_notify_socket() {
+        let path = std::env::temp_dir().join(format!("ua4f-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", &path);
+
+        notify("READY=1").unwrap();
+        let mut buf = [0; 64];
+        let n = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+
+        std::env::remove_var("NOTIFY_SOCKET");
+        notify("READY=1").unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn watchdog_interval_only_applies_to_this_process() {
+        std::env::set_var("WATCHDOG_USEC", "2000000");
+        std::env::remove_var("WATCHDOG_PID");
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+
+        std::env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+        std::env::set_var("WATCHDOG_PID", (std::process::id() + 1).to_string());
+        assert_eq!(watchdog_interval(), None);
+
+        std::env::remove_var("WATCHDOG_PID");
+        std::env::set_var("WATCHDOG_USEC", "0");
+        assert_eq!(watchdog_interval(), None);
+        std::env::remove_var("WATCHDOG_USEC");
+        assert_eq!(watchdog_interval(), None);
+    }
+}
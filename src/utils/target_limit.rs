@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use moka::future::Cache;
+
+/// 同时跟踪的客户端 IP 数上限，超出后按 LRU 淘汰
+const MAX_TRACKED_CLIENTS: u64 = 10_000;
+
+#[derive(Default)]
+struct ClientTargets {
+    targets: HashSet<String>,
+    /// 当前窗口内是否已经报告过超限
+    exceeded: bool,
+}
+
+/// 记录目标后的判定结果
+pub enum TargetCheck {
+    Within,
+    /// 新目标超出上限；`first` 表示本窗口内首次超限
+    Exceeded { first: bool },
+}
+
+/// 统计每个客户端 IP 在固定时间窗口内连接过的不同 `host:port` 目标数，
+/// 每个客户端最多保存 `limit` 个目标
+pub struct TargetTracker {
+    limit: usize,
+    clients: Cache<IpAddr, Arc<Mutex<ClientTargets>>>,
+}
+
+impl TargetTracker {
+    /// 窗口从客户端在窗口内的第一次连接开始计算，到期后重新计数
+    pub fn new(limit: usize, window: Duration) -> Self {
+        TargetTracker {
+            limit,
+            clients: Cache::builder()
+                .max_capacity(MAX_TRACKED_CLIENTS)
+                .time_to_live(window)
+                .build(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// 记录客户端连接的目标；已记录过的目标始终视为未超限
+    pub async fn record(&self, client: IpAddr, target: &str) -> TargetCheck {
+        let entry = self.clients.get_with(client, async { Arc::default() }).await;
+        let mut entry = entry.lock().unwrap();
+        if entry.targets.contains(target) {
+            return TargetCheck::Within;
+        }
+        if entry.targets.len() < self.limit {
+            entry.targets.insert(target.to_string());
+            return TargetCheck::Within;
+        }
+        let first = !entry.exceeded;
+        entry.exceeded = true;
+        TargetCheck::Exceeded { first }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+
+    #[tokio::test]
+    async fn new_targets_beyond_the_limit_are_flagged_once() {
+        let tracker = TargetTracker::new(2, Duration::from_secs(60));
+        assert!(matches!(tracker.record(CLIENT, "a:80").await, TargetCheck::Within));
+        assert!(matches!(tracker.record(CLIENT, "b:80").await, TargetCheck::Within));
+        assert!(matches!(tracker.record(CLIENT, "c:80").await, TargetCheck::Exceeded { first: true }));
+        assert!(matches!(tracker.record(CLIENT, "d:80").await, TargetCheck::Exceeded { first: false }));
+        // 已记录过的目标不受影响
+        assert!(matches!(tracker.record(CLIENT, "a:80").await, TargetCheck::Within));
+    }
+
+    #[tokio::test]
+    async fn clients_are_counted_separately() {
+        let tracker = TargetTracker::new(1, Duration::from_secs(60));
+        let other = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2));
+        assert!(matches!(tracker.record(CLIENT, "a:80").await, TargetCheck::Within));
+        assert!(matches!(tracker.record(other, "b:80").await, TargetCheck::Within));
+        assert!(matches!(tracker.record(CLIENT, "b:80").await, TargetCheck::Exceeded { first: true }));
+    }
+
+    #[tokio::test]
+    async fn the_count_restarts_after_the_window() {
+        let tracker = TargetTracker::new(1, Duration::from_millis(200));
+        assert!(matches!(tracker.record(CLIENT, "a:80").await, TargetCheck::Within));
+        assert!(matches!(tracker.record(CLIENT, "b:80").await, TargetCheck::Exceeded { first: true }));
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(matches!(tracker.record(CLIENT, "b:80").await, TargetCheck::Within));
+    }
+}
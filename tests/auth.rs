@@ -0,0 +1,61 @@
+//! SOCKS5 用户名/密码认证与按用户选取 User-Agent 的集成测试
+mod common;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+
+/// 写出凭据文件，密码以最低成本的 bcrypt 哈希保存
+fn auth_file(name: &str, users: &[(&str, &str)]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ua4f-auth-{}-{}.txt", name, std::process::id()));
+    let content: String = users
+        .iter()
+        .map(|(user, pass)| format!("{}:{}\n", user, bcrypt::hash(pass, 4).unwrap()))
+        .collect();
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+/// 以 `user` 登录并经代理发送一个请求，返回目标收到的 User-Agent
+async fn user_agent_as(proxy: &Proxy, user: &str, pass: &str) -> Option<String> {
+    let target = Target::sink().await;
+    let (accepted, mut stream) = socks5_login(proxy.addr, user, pass).await;
+    assert!(accepted, "{user} 认证失败");
+    assert_eq!(socks5_request(&mut stream, target.addr).await, 0);
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    user_agent(&target.wait_heads(0, 1, Duration::from_secs(5)).await)
+}
+
+#[tokio::test]
+async fn each_user_gets_its_configured_user_agent() {
+    let path = auth_file("users", &[("mobile", "m-pass"), ("desktop", "d-pass"), ("other", "o-pass")]);
+    let proxy = Proxy::spawn(&[
+        "--auth-file",
+        path.to_str().unwrap(),
+        "-f",
+        "Global/1.0",
+        "--user-user-agent",
+        "mobile=Mobile/1.0",
+        "--user-user-agent",
+        "desktop=Desktop/1.0",
+    ])
+    .await;
+
+    assert_eq!(user_agent_as(&proxy, "mobile", "m-pass").await.as_deref(), Some("Mobile/1.0"));
+    assert_eq!(user_agent_as(&proxy, "desktop", "d-pass").await.as_deref(), Some("Desktop/1.0"));
+    // 未配置的用户使用全局值
+    assert_eq!(user_agent_as(&proxy, "other", "o-pass").await.as_deref(), Some("Global/1.0"));
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn wrong_password_is_rejected() {
+    let path = auth_file("reject", &[("mobile", "m-pass")]);
+    let proxy = Proxy::spawn(&["--auth-file", path.to_str().unwrap()]).await;
+
+    let (accepted, _stream) = socks5_login(proxy.addr, "mobile", "wrong").await;
+    assert!(!accepted);
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,319 @@
+//! 集成测试的公共工具：以子进程启动 ua4f，并提供原始 SOCKS5 客户端与本地目标服务
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 以子进程运行的 ua4f，drop 时结束进程
+pub struct Proxy {
+    pub addr: SocketAddr,
+    child: Child,
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+/// 取一个当前空闲的本地端口
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+impl Proxy {
+    /// 以 `args` 启动 ua4f，监听 127.0.0.1 上的空闲端口，等到开始监听后返回
+    pub async fn spawn(args: &[&str]) -> Proxy {
+        Proxy::spawn_with(args, |_| {}).await
+    }
+
+    /// 同 [`Proxy::spawn`]，启动前可调整命令（如设置环境变量）
+    pub async fn spawn_with(args: &[&str], configure: impl FnOnce(&mut Command)) -> Proxy {
+        let port = free_port();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ua4f"));
+        command
+            .args(["-b", "127.0.0.1", "-p", &port.to_string(), "--no-file-log"])
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        configure(&mut command);
+        let mut child = command.spawn().unwrap();
+
+        // 持续读取输出，避免管道写满后 ua4f 阻塞在日志输出上
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        for output in [
+            Box::new(child.stdout.take().unwrap()) as Box<dyn std::io::Read + Send>,
+            Box::new(child.stderr.take().unwrap()),
+        ] {
+            let logs = logs.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(output).lines() {
+                    let Ok(line) = line else { break };
+                    logs.lock().unwrap().push(line);
+                }
+            });
+        }
+
+        let mut proxy = Proxy { addr: SocketAddr::from(([127, 0, 0, 1], port)), child, logs };
+        let listening = format!("Listening on {}", proxy.addr);
+        if !proxy.wait_for_log(&listening, Duration::from_secs(10)).await {
+            let status = proxy.child.try_wait().unwrap();
+            panic!("ua4f 未能开始监听（{:?}）：\n{}", status, proxy.logs().join("\n"));
+        }
+        proxy
+    }
+
+    /// 目前为止的全部输出行
+    pub fn logs(&self) -> Vec<String> {
+        self.logs.lock().unwrap().clone()
+    }
+
+    /// 包含 `needle` 的输出行数
+    pub fn log_count(&self, needle: &str) -> usize {
+        self.logs.lock().unwrap().iter().filter(|line| line.contains(needle)).count()
+    }
+
+    /// 在 `timeout` 内等待出现包含 `needle` 的输出行
+    pub async fn wait_for_log(&self, needle: &str, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if self.log_count(needle) > 0 {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// 等待进程自行退出，超时返回 None
+    pub async fn wait_exit(&mut self, timeout: Duration) -> Option<std::process::ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return Some(status);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        None
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 完成无认证的 SOCKS5 握手
+pub async fn socks5_greet(proxy: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream.write_all(&[5, 1, 0]).await.unwrap();
+    let mut method = [0; 2];
+    stream.read_exact(&mut method).await.unwrap();
+    assert_eq!(method, [5, 0]);
+    stream
+}
+
+/// 以用户名/密码认证完成 SOCKS5 握手，返回认证是否通过
+pub async fn socks5_login(proxy: SocketAddr, user: &str, pass: &str) -> (bool, TcpStream) {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream.write_all(&[5, 1, 2]).await.unwrap();
+    let mut method = [0; 2];
+    stream.read_exact(&mut method).await.unwrap();
+    assert_eq!(method, [5, 2]);
+    let mut request = vec![1, user.len() as u8];
+    request.extend_from_slice(user.as_bytes());
+    request.push(pass.len() as u8);
+    request.extend_from_slice(pass.as_bytes());
+    stream.write_all(&request).await.unwrap();
+    let mut status = [0; 2];
+    stream.read_exact(&mut status).await.unwrap();
+    (status[1] == 0, stream)
+}
+
+/// 在已完成握手的连接上请求 CONNECT 到 `target`，返回应答码
+pub async fn socks5_request(stream: &mut TcpStream, target: SocketAddr) -> u8 {
+    let SocketAddr::V4(target) = target else { panic!("仅支持 IPv4 目标") };
+    let mut request = vec![5, 1, 0, 1];
+    request.extend_from_slice(&target.ip().octets());
+    request.extend_from_slice(&target.port().to_be_bytes());
+    stream.write_all(&request).await.unwrap();
+    read_reply(stream).await.0
+}
+
+/// 读取 SOCKS5 应答，返回应答码与 BND.ADDR:BND.PORT
+pub async fn read_reply(stream: &mut TcpStream) -> (u8, SocketAddr) {
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await.unwrap();
+    let bound = match reply[3] {
+        1 => {
+            let mut addr = [0; 6];
+            stream.read_exact(&mut addr).await.unwrap();
+            SocketAddr::from(([addr[0], addr[1], addr[2], addr[3]], u16::from_be_bytes([addr[4], addr[5]])))
+        }
+        4 => {
+            let mut addr = [0; 18];
+            stream.read_exact(&mut addr).await.unwrap();
+            let ip: [u8; 16] = addr[..16].try_into().unwrap();
+            SocketAddr::from((ip, u16::from_be_bytes([addr[16], addr[17]])))
+        }
+        atyp => panic!("意外的地址类型 {atyp}"),
+    };
+    (reply[1], bound)
+}
+
+/// 以原始 SOCKS5 协议（无认证）请求 CONNECT 到 `target`，返回应答码与连接
+pub async fn socks5_connect(proxy: SocketAddr, target: SocketAddr) -> (u8, TcpStream) {
+    let (reply, stream, _) = socks5_connect_bound(proxy, target).await;
+    (reply, stream)
+}
+
+/// 同 [`socks5_connect`]，另外返回应答中的 BND.ADDR:BND.PORT
+pub async fn socks5_connect_bound(proxy: SocketAddr, target: SocketAddr) -> (u8, TcpStream, SocketAddr) {
+    let mut stream = socks5_greet(proxy).await;
+    let SocketAddr::V4(target) = target else { panic!("仅支持 IPv4 目标") };
+    let mut request = vec![5, 1, 0, 1];
+    request.extend_from_slice(&target.ip().octets());
+    request.extend_from_slice(&target.port().to_be_bytes());
+    stream.write_all(&request).await.unwrap();
+    let (reply, bound) = read_reply(&mut stream).await;
+    (reply, stream, bound)
+}
+
+/// 一个连接目前收到的数据
+type Received = Arc<Mutex<Vec<u8>>>;
+
+/// 记录收到的全部数据的本地目标服务
+pub struct Target {
+    pub addr: SocketAddr,
+    /// 每个已接受的连接收到的数据，按接受顺序排列
+    received: Arc<Mutex<Vec<Received>>>,
+    /// 已关闭（读到 EOF 或出错）的连接数
+    closed: Arc<AtomicUsize>,
+}
+
+impl Target {
+    /// 只接收不回复的目标
+    pub async fn sink() -> Target {
+        Target::spawn(|_| Vec::new()).await
+    }
+
+    /// 对每段收到的数据回复 `respond(data)` 的目标
+    pub async fn spawn(respond: impl Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static) -> Target {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let received: Arc<Mutex<Vec<Received>>> = Arc::default();
+        let closed = Arc::new(AtomicUsize::new(0));
+        let respond = Arc::new(respond);
+        let (connections, closed_count) = (received.clone(), closed.clone());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let data = Arc::new(Mutex::new(Vec::new()));
+                connections.lock().unwrap().push(data.clone());
+                let (respond, closed) = (respond.clone(), closed_count.clone());
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 64 * 1024];
+                    loop {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => {
+                                data.lock().unwrap().extend_from_slice(&buf[..n]);
+                                let response = respond(&buf[..n]);
+                                if !response.is_empty() && stream.write_all(&response).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    closed.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+        Target { addr, received, closed }
+    }
+
+    /// 已接受的连接数
+    pub fn accepted(&self) -> usize {
+        self.received.lock().unwrap().len()
+    }
+
+    /// 第 `index` 个连接目前收到的数据
+    pub fn received(&self, index: usize) -> Vec<u8> {
+        self.received.lock().unwrap()[index].lock().unwrap().clone()
+    }
+
+    /// 在 `timeout` 内等待第 `index` 个连接收到的数据满足 `done`
+    pub async fn wait_until(&self, index: usize, timeout: Duration, done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if self.accepted() > index {
+                let data = self.received(index);
+                if done(&data) {
+                    return data;
+                }
+            }
+            if tokio::time::Instant::now() >= deadline {
+                panic!("目标在 {:?} 内未收到预期的数据", timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// 在 `timeout` 内等待第 `index` 个连接收到至少 `len` 字节
+    pub async fn wait_received(&self, index: usize, len: usize, timeout: Duration) -> Vec<u8> {
+        self.wait_until(index, timeout, |data| data.len() >= len).await
+    }
+
+    /// 在 `timeout` 内等待第 `index` 个连接收到 `count` 个完整的请求头
+    pub async fn wait_heads(&self, index: usize, count: usize, timeout: Duration) -> Vec<u8> {
+        self.wait_until(index, timeout, |data| data.windows(4).filter(|w| *w == b"\r\n\r\n").count() >= count).await
+    }
+
+    /// 已关闭的连接数
+    pub fn closed(&self) -> usize {
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+/// 读到 EOF 或出错为止，超时返回 None
+pub async fn read_until_closed(stream: &mut TcpStream, timeout: Duration) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    tokio::time::timeout(timeout, async {
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+            }
+        }
+    })
+    .await
+    .ok()?;
+    Some(data)
+}
+
+/// `data` 中 User-Agent 头的值
+pub fn user_agent(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.split("\r\n")
+        .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("User-Agent")))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// `--log-format json` 输出中 message 为 `message` 的事件
+pub fn json_events(proxy: &Proxy, message: &str) -> Vec<serde_json::Value> {
+    proxy
+        .logs()
+        .iter()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|event| event["fields"]["message"] == message)
+        .collect()
+}
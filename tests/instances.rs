@@ -0,0 +1,66 @@
+//! 配置文件中 `[[instance]]` 多实例的集成测试
+mod common;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+
+fn config_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ua4f-instances-{}-{}.toml", name, std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+/// 经 `proxy` 发送一个请求，返回目标收到的 User-Agent
+async fn forwarded_user_agent(proxy: SocketAddr) -> Option<String> {
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    user_agent(&target.wait_heads(0, 1, Duration::from_secs(5)).await)
+}
+
+#[tokio::test]
+async fn each_instance_rewrites_to_its_own_user_agent() {
+    let (second, plain) = (free_port(), free_port());
+    let path = config_file(
+        "two",
+        &format!(
+            "[[instance]]\nport = \"{second}\"\nuser-agent = \"Second/1.0\"\n\n[[instance]]\nport = \"{plain}\"\nno_rewrite = true\n"
+        ),
+    );
+    let proxy = Proxy::spawn(&["--config", path.to_str().unwrap(), "-f", "Main/1.0"]).await;
+    let second = SocketAddr::from(([127, 0, 0, 1], second));
+    let plain = SocketAddr::from(([127, 0, 0, 1], plain));
+    for addr in [second, plain] {
+        assert!(proxy.wait_for_log(&format!("Listening on {}", addr), Duration::from_secs(5)).await);
+    }
+
+    assert_eq!(forwarded_user_agent(proxy.addr).await.as_deref(), Some("Main/1.0"));
+    assert_eq!(forwarded_user_agent(second).await.as_deref(), Some("Second/1.0"));
+    assert_eq!(forwarded_user_agent(plain).await.as_deref(), Some("curl/8.4.0"));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn shared_settings_are_rejected_inside_an_instance() {
+    for (name, instance, error) in [
+        ("shared", "port = \"1\"\nmax-connections = 10\n", "`max-connections` cannot be set per instance"),
+        ("same-bind", "user_agent = \"Second/1.0\"\n", "`bind` or `port` must differ"),
+    ] {
+        let path = config_file(name, &format!("[[instance]]\n{instance}"));
+        let output = Command::new(env!("CARGO_BIN_EXE_ua4f"))
+            .args(["-b", "127.0.0.1", "-p", &free_port().to_string(), "--no-file-log"])
+            .args(["--config", path.to_str().unwrap()])
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_file(path);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("instance #1: {}", error)), "{}", stderr);
+    }
+}
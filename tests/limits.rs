@@ -0,0 +1,110 @@
+//! 连接数、速率与目标数等限制的集成测试
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn connections_beyond_the_rate_are_delayed() {
+    let proxy = Proxy::spawn(&["--max-conn-rate", "2", "--conn-rate-burst", "2"]).await;
+
+    // 同时发起 6 个连接：突发的 2 个立即完成握手，其余按每秒 2 个放行
+    let started = Instant::now();
+    let greetings: Vec<_> = (0..6)
+        .map(|_| {
+            let addr = proxy.addr;
+            tokio::spawn(async move {
+                let _stream = socks5_greet(addr).await;
+                started.elapsed()
+            })
+        })
+        .collect();
+    let mut elapsed = Vec::new();
+    for greeting in greetings {
+        elapsed.push(greeting.await.unwrap());
+    }
+    elapsed.sort();
+
+    assert!(elapsed[1] < Duration::from_millis(400), "{elapsed:?}");
+    assert!(elapsed[2] >= Duration::from_millis(400), "{elapsed:?}");
+    assert!(elapsed[5] >= Duration::from_millis(1800), "{elapsed:?}");
+}
+
+/// 依次经代理连接 `targets`，返回各自的 SOCKS 应答码
+async fn connect_all(proxy: &Proxy, targets: &[Target]) -> Vec<u8> {
+    let mut replies = Vec::new();
+    for target in targets {
+        replies.push(socks5_connect(proxy.addr, target.addr).await.0);
+    }
+    replies
+}
+
+#[tokio::test]
+async fn many_targets_from_one_client_trigger_a_warning() {
+    let proxy = Proxy::spawn(&["--max-targets-per-client", "2"]).await;
+    let targets = [Target::sink().await, Target::sink().await, Target::sink().await, Target::sink().await];
+
+    assert_eq!(connect_all(&proxy, &targets).await, [0, 0, 0, 0]);
+    assert!(proxy.wait_for_log("疑似扫描", Duration::from_secs(5)).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(proxy.log_count("疑似扫描"), 1, "每个窗口只告警一次");
+}
+
+#[tokio::test]
+async fn excess_targets_are_refused_with_refuse_excess_targets() {
+    let proxy = Proxy::spawn(&["--max-targets-per-client", "2", "--refuse-excess-targets"]).await;
+    let targets = [Target::sink().await, Target::sink().await, Target::sink().await];
+
+    // 第三个不同目标被拒绝（ConnectionNotAllowed），已连接过的目标不受影响
+    assert_eq!(connect_all(&proxy, &targets).await, [0, 0, 2]);
+    assert_eq!(connect_all(&proxy, &targets[..1]).await, [0]);
+    assert!(proxy.wait_for_log("疑似扫描", Duration::from_secs(5)).await);
+    assert_eq!(targets[2].accepted(), 0);
+}
+
+/// 发送 SOCKS5 问候，在 `timeout` 内等待方法选择应答；连接被关闭时返回 Some(None)，超时返回 None
+async fn greet_within(stream: &mut TcpStream, timeout: Duration) -> Option<Option<[u8; 2]>> {
+    stream.write_all(&[5, 1, 0]).await.unwrap();
+    let mut method = [0; 2];
+    match tokio::time::timeout(timeout, stream.read_exact(&mut method)).await {
+        Ok(Ok(_)) => Some(Some(method)),
+        Ok(Err(_)) => Some(None),
+        Err(_) => None,
+    }
+}
+
+#[tokio::test]
+async fn queued_connection_is_served_once_a_slot_frees_up() {
+    let proxy = Proxy::spawn(&["--max-connections", "1", "--accept-queue-len", "1", "--accept-queue-timeout", "10"]).await;
+    let target = Target::sink().await;
+    let (reply, first) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+
+    // 第二个连接排队，暂不握手；第三个超出队列长度，直接拒绝
+    let mut queued = TcpStream::connect(proxy.addr).await.unwrap();
+    assert_eq!(greet_within(&mut queued, Duration::from_millis(500)).await, None);
+    let mut refused = TcpStream::connect(proxy.addr).await.unwrap();
+    assert_eq!(greet_within(&mut refused, Duration::from_secs(5)).await, Some(None));
+
+    drop(first);
+    let mut method = [0; 2];
+    tokio::time::timeout(Duration::from_secs(5), queued.read_exact(&mut method)).await.unwrap().unwrap();
+    assert_eq!(method, [5, 0]);
+}
+
+#[tokio::test]
+async fn queued_connection_is_refused_after_the_queue_timeout() {
+    let proxy = Proxy::spawn(&["--max-connections", "1", "--accept-queue-len", "1", "--accept-queue-timeout", "1"]).await;
+    let target = Target::sink().await;
+    let (reply, _first) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+
+    let started = Instant::now();
+    let mut queued = TcpStream::connect(proxy.addr).await.unwrap();
+    assert_eq!(greet_within(&mut queued, Duration::from_secs(5)).await, Some(None));
+    assert!(started.elapsed() >= Duration::from_millis(900), "{:?}", started.elapsed());
+    assert!(proxy.wait_for_log("的连接排队 1s 仍无空位，拒绝连接", Duration::from_secs(5)).await);
+}
@@ -0,0 +1,59 @@
+//! 多个监听地址与按监听地址细分的统计的集成测试
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// 从 /metrics 端点读取指标 `name{listener="<listener>"}` 的值
+async fn listener_metric(metrics: SocketAddr, name: &str, listener: SocketAddr) -> u64 {
+    let mut stream = loop {
+        // 指标端点可能稍晚于 SOCKS 监听启动
+        match TcpStream::connect(metrics).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: metrics\r\n\r\n").await.unwrap();
+    let response = read_until_closed(&mut stream, Duration::from_secs(5)).await.unwrap();
+    let prefix = format!("{name}{{listener=\"{listener}\"}} ");
+    String::from_utf8(response)
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix).map(|value| value.parse().unwrap()))
+        .unwrap_or_else(|| panic!("/metrics 中没有 {prefix}"))
+}
+
+#[tokio::test]
+async fn connection_counts_are_tracked_per_listener() {
+    let second = SocketAddr::from(([127, 0, 0, 1], free_port()));
+    let metrics = SocketAddr::from(([127, 0, 0, 1], free_port()));
+    let proxy = Proxy::spawn(&["-b", &second.to_string(), "--metrics-addr", &metrics.to_string()]).await;
+    assert!(proxy.wait_for_log(&format!("Listening on {second}"), Duration::from_secs(5)).await);
+    let target = Target::sink().await;
+
+    let (reply, first_a) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    let (reply, _first_b) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    let (reply, _second_a) = socks5_connect(second, target.addr).await;
+    assert_eq!(reply, 0);
+
+    assert_eq!(listener_metric(metrics, "ua4f_listener_connections_total", proxy.addr).await, 2);
+    assert_eq!(listener_metric(metrics, "ua4f_listener_connections_total", second).await, 1);
+    assert_eq!(listener_metric(metrics, "ua4f_listener_active_connections", proxy.addr).await, 2);
+    assert_eq!(listener_metric(metrics, "ua4f_listener_active_connections", second).await, 1);
+
+    // 一个连接结束后只影响其所属监听地址的活跃数
+    drop(first_a);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while listener_metric(metrics, "ua4f_listener_active_connections", proxy.addr).await != 1 {
+        assert!(tokio::time::Instant::now() < deadline, "连接关闭后活跃数未下降");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(listener_metric(metrics, "ua4f_listener_active_connections", second).await, 1);
+    assert_eq!(listener_metric(metrics, "ua4f_listener_connections_total", proxy.addr).await, 2);
+}
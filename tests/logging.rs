@@ -0,0 +1,36 @@
+//! 日志配置的集成测试
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn invalid_log_level_falls_back_to_info_with_a_warning() {
+    // 能等到 info 级别的 "Listening on" 即说明已回退到 info
+    let proxy = Proxy::spawn(&["-l", "ua4f=notalevel"]).await;
+    assert_eq!(proxy.log_count("[Warning] Invalid log level \"ua4f=notalevel\""), 1);
+    assert!(proxy.log_count("Falling back to info") > 0);
+
+    // debug 级别的连接日志不输出
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(proxy.log_count(" DEBUG "), 0, "{:?}", proxy.logs());
+}
+
+#[tokio::test]
+async fn valid_log_level_is_used_without_a_warning() {
+    let proxy = Proxy::spawn(&["-l", "debug"]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"GET / HTTP/1.1\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await;
+    assert!(proxy.wait_for_log(" DEBUG ", Duration::from_secs(5)).await);
+    assert_eq!(proxy.log_count("Invalid log level"), 0);
+}
@@ -0,0 +1,80 @@
+//! 非 HTTP 缓存的集成测试：嗅探结果的缓存、确认阈值与预热
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// 命中非 HTTP 缓存、跳过嗅探时的日志
+const CACHE_HIT: &str = "目标缓存为非 HTTP，直接转发流量";
+
+/// 原样返回收到数据的目标
+async fn echo_target() -> Target {
+    Target::spawn(|data| data.to_vec()).await
+}
+
+/// 经代理向 `target` 发送一段 TLS 形式的数据并等待回显，之后关闭连接
+async fn send_non_http(proxy: &Proxy, target: SocketAddr) {
+    let (reply, mut stream) = socks5_connect(proxy.addr, target).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"\x16\x03\x01hello").await.unwrap();
+    let mut echoed = [0; 8];
+    stream.read_exact(&mut echoed).await.unwrap();
+}
+
+#[tokio::test]
+async fn target_is_cached_only_after_the_confirmation_threshold() {
+    let proxy = Proxy::spawn(&["-l", "debug", "--non-http-confirmations", "2"]).await;
+    let target = echo_target().await;
+
+    // 第一次观察到非 HTTP 时暂不缓存，第二个连接仍然嗅探
+    send_non_http(&proxy, target.addr).await;
+    send_non_http(&proxy, target.addr).await;
+    // 日志经管道异步到达，留出时间让可能出现的命中日志被读到
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(proxy.log_count(CACHE_HIT), 0);
+
+    // 第二次观察后写入缓存，第三个连接直接转发
+    send_non_http(&proxy, target.addr).await;
+    assert!(proxy.wait_for_log(CACHE_HIT, Duration::from_secs(5)).await);
+    assert_eq!(proxy.log_count(CACHE_HIT), 1);
+}
+
+#[tokio::test]
+async fn warmed_up_target_skips_the_sniff_on_its_first_connection() {
+    let target = echo_target().await;
+    let hosts = std::env::temp_dir().join(format!("ua4f-warmup-{}-{}.txt", std::process::id(), target.addr.port()));
+    std::fs::write(&hosts, format!("# 预热列表\n{}\n", target.addr)).unwrap();
+    let proxy = Proxy::spawn(&["-l", "debug", "--warmup-hosts", hosts.to_str().unwrap()]).await;
+
+    send_non_http(&proxy, target.addr).await;
+    assert!(proxy.wait_for_log(CACHE_HIT, Duration::from_secs(5)).await);
+    let _ = std::fs::remove_file(hosts);
+}
+
+#[tokio::test]
+async fn reuse_count_is_recorded_when_an_entry_expires() {
+    let proxy = Proxy::spawn(&["--non-http-cache-ttl", "1", "--cache-stats-interval", "1", "--log-format", "json"]).await;
+    let target = echo_target().await;
+
+    // 第一个连接写入缓存，之后三个连接各复用一次
+    for _ in 0..4 {
+        send_non_http(&proxy, target.addr).await;
+    }
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    let reuse = loop {
+        let expired = json_events(&proxy, "非 HTTP 缓存复用次数分布")
+            .into_iter()
+            .map(|event| event["fields"]["reuse"].as_str().unwrap().to_string())
+            .find(|reuse| reuse != "0:0 1:0 2-4:0 5-16:0 >16:0");
+        if let Some(reuse) = expired {
+            break reuse;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "缓存条目过期后未记录复用次数");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(reuse, "0:0 1:0 2-4:1 5-16:0 >16:0");
+}
@@ -0,0 +1,50 @@
+//! OTLP trace 导出的集成测试，需启用 otel 特性
+#![cfg(all(unix, feature = "otel"))]
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+
+/// `haystack` 中是否出现 `needle`；OTLP 以 protobuf 编码，字符串字段按原样出现
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[tokio::test]
+async fn one_connection_exports_a_span_with_its_phases() {
+    // 只回复请求头所在分段的 OTLP/HTTP 收集端
+    let collector = Target::spawn(|data| {
+        if data.starts_with(b"POST ") {
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/x-protobuf\r\nContent-Length: 0\r\n\r\n".to_vec()
+        } else {
+            Vec::new()
+        }
+    })
+    .await;
+    let endpoint = format!("http://{}/v1/traces", collector.addr);
+    let mut proxy = Proxy::spawn(&["--otlp-endpoint", &endpoint, "-f", "Replaced/1.0"]).await;
+
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await;
+    stream.shutdown().await.unwrap();
+    read_until_closed(&mut stream, Duration::from_secs(5)).await.unwrap();
+
+    // 退出时导出批量队列中剩余的 span
+    assert_eq!(unsafe { libc::kill(proxy.pid() as libc::pid_t, libc::SIGTERM) }, 0);
+    assert!(proxy.wait_exit(Duration::from_secs(10)).await.is_some());
+
+    let exported = collector.wait_until(0, Duration::from_secs(5), |data| contains(data, b"relay")).await;
+    assert!(contains(&exported, b"POST /v1/traces"));
+    assert!(contains(&exported, b"ua4f"));
+    for name in [&b"conn"[..], b"connect", b"sniff", b"rewrite", b"relay"] {
+        assert!(contains(&exported, name), "缺少 span {}", String::from_utf8_lossy(name));
+    }
+    assert!(contains(&exported, target.addr.to_string().as_bytes()));
+    assert!(contains(&exported, b"upload"));
+    assert!(contains(&exported, b"download"));
+}
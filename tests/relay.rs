@@ -0,0 +1,173 @@
+//! 建立连接后客户端与目标之间转发阶段的集成测试
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use common::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn progress_is_logged_during_a_slow_transfer() {
+    let proxy = Proxy::spawn(&["-l", "debug", "--progress-interval", "1"]).await;
+    let target = Target::sink().await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    // 约 2.5 秒内陆续上传，期间至少经过两个进度间隔
+    for _ in 0..10 {
+        stream.write_all(&[0x16; 512]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    let progress = format!("连接 {} 传输进度", target.addr);
+    let lines: Vec<String> = proxy.logs().into_iter().filter(|line| line.contains(&progress)).collect();
+    assert!(!lines.is_empty(), "连接仍在传输时没有进度日志");
+    assert!(lines.iter().any(|line| !line.contains("上行 0 字节")), "{lines:?}");
+    drop(stream);
+}
+
+#[tokio::test]
+async fn progress_is_not_logged_without_the_interval() {
+    let proxy = Proxy::spawn(&["-l", "debug"]).await;
+    let target = Target::sink().await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(&[0x16; 512]).await.unwrap();
+    target.wait_received(0, 512, Duration::from_secs(5)).await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(proxy.log_count("传输进度"), 0);
+}
+
+/// 接受一个连接后只读不写、读到 EOF 也不关闭的目标，模拟半开后不再响应的远端
+async fn spawn_silent_target() -> (SocketAddr, tokio::task::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let task = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 1024];
+        while matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {}
+        std::future::pending::<()>().await;
+    });
+    (addr, task)
+}
+
+#[tokio::test]
+async fn half_open_connection_is_torn_down_after_the_timeout() {
+    let proxy = Proxy::spawn(&["-l", "debug", "--half-open-timeout", "1"]).await;
+    let (target, _task) = spawn_silent_target().await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target).await;
+    assert_eq!(reply, 0);
+    // 先让转发阶段开始，再关闭客户端的写方向
+    stream.write_all(b"hello").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    stream.shutdown().await.unwrap();
+    let half_closed = Instant::now();
+    assert_eq!(read_until_closed(&mut stream, Duration::from_secs(5)).await, Some(Vec::new()));
+    assert!(half_closed.elapsed() >= Duration::from_millis(900), "{:?}", half_closed.elapsed());
+    assert!(proxy.wait_for_log(&format!("连接 {target} 半开且无数据传输超时"), Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn half_open_connection_stays_open_without_the_timeout() {
+    let proxy = Proxy::spawn(&["-l", "debug"]).await;
+    let (target, _task) = spawn_silent_target().await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target).await;
+    assert_eq!(reply, 0);
+    // 先让转发阶段开始，再关闭客户端的写方向
+    stream.write_all(b"hello").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    stream.shutdown().await.unwrap();
+    assert_eq!(read_until_closed(&mut stream, Duration::from_millis(2000)).await, None);
+    assert_eq!(proxy.log_count("半开且无数据传输超时"), 0);
+}
+
+#[tokio::test]
+async fn non_http_stream_reaches_the_target_intact() {
+    let proxy = Proxy::spawn(&[]).await;
+    let target = Target::sink().await;
+    let payload: Vec<u8> = (0..64 * 1024).map(|i| (i * 7 % 251) as u8).collect();
+
+    // 第一次嗅探后写入非 HTTP 缓存，第二次直接转发；两种路径都不能丢失开头的字节
+    for index in 0..2 {
+        let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+        assert_eq!(reply, 0);
+        stream.write_all(&payload[..3]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(&payload[3..]).await.unwrap();
+        assert_eq!(target.wait_received(index, payload.len(), Duration::from_secs(5)).await, payload);
+    }
+}
+
+#[tokio::test]
+async fn upload_containing_a_block_pattern_tears_down_the_connection() {
+    let proxy = Proxy::spawn(&["--block-pattern", "FORBIDDEN"]).await;
+    let target = Target::sink().await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"\x00\x01harmless").await.unwrap();
+    target.wait_received(0, 10, Duration::from_secs(5)).await;
+    // 模式跨越两次写入
+    stream.write_all(b"...FORBID").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let _ = stream.write_all(b"DEN...").await;
+
+    assert!(read_until_closed(&mut stream, Duration::from_secs(5)).await.is_some(), "命中模式后连接未被拆除");
+    assert!(proxy.wait_for_log("的上行数据命中内容过滤模式 \"FORBIDDEN\"，拆除连接", Duration::from_secs(5)).await);
+    // 命中的数据段不会转发给目标
+    assert!(!String::from_utf8_lossy(&target.received(0)).contains("FORBIDDEN"));
+}
+
+#[tokio::test]
+async fn connection_is_torn_down_after_the_read_timeout() {
+    let proxy = Proxy::spawn(&["--read-timeout", "1"]).await;
+    let target = Target::sink().await;
+
+    // 客户端建立连接后不发送数据，等待代理在 --read-timeout 后关闭两端
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    assert!(read_until_closed(&mut stream, Duration::from_secs(5)).await.is_some(), "代理未关闭客户端连接");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while target.closed() < 1 {
+        assert!(Instant::now() < deadline, "代理未关闭目标连接");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// 经代理发送请求头，50ms 后再发送 body，返回目标每次读到的数据
+async fn initial_reads(args: &[&str]) -> Vec<Vec<u8>> {
+    let proxy = Proxy::spawn(args).await;
+    let reads = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = reads.clone();
+    let target = Target::spawn(move |data| {
+        recorded.lock().unwrap().push(data.to_vec());
+        Vec::new()
+    })
+    .await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"POST /submit HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\n").await.unwrap();
+    // body 稍后到达，仍在合并首次写入的等待时间之内
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(b"hello").await.unwrap();
+    target.wait_until(0, Duration::from_secs(5), |data| data.ends_with(b"hello")).await;
+    let reads = reads.lock().unwrap().clone();
+    reads
+}
+
+#[tokio::test]
+async fn coalesced_request_reaches_the_target_in_one_write() {
+    let reads = initial_reads(&["--coalesce-initial-write"]).await;
+    assert_eq!(reads, [b"POST /submit HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello".to_vec()]);
+
+    // 未开启时请求头先行写入
+    let reads = initial_reads(&[]).await;
+    assert_eq!(reads.len(), 2, "{reads:?}");
+    assert!(reads[0].ends_with(b"\r\n\r\n"));
+}
@@ -0,0 +1,161 @@
+//! User-Agent 改写的端到端集成测试：经代理发送 HTTP 请求，检查目标实际收到的数据
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const REQUEST: &[u8] = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: curl/8.4.0\r\nAccept: */*\r\n\r\n";
+
+/// 经代理发送 `request`，等目标收到完整的请求头后返回目标收到的数据
+async fn forward(proxy: &Proxy, request: &[u8]) -> Vec<u8> {
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(request).await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await
+}
+
+#[tokio::test]
+async fn no_rewrite_forwards_requests_unmodified_without_sniffing() {
+    let proxy = Proxy::spawn(&["--no-rewrite", "-f", "Replaced/1.0"]).await;
+    assert_eq!(forward(&proxy, REQUEST).await, REQUEST);
+
+    // 目标先发数据：未嗅探时客户端不发送任何数据也能立即收到
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let banner_target = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        stream.write_all(b"220 ready\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+    let (reply, mut stream) = socks5_connect(proxy.addr, banner_target).await;
+    assert_eq!(reply, 0);
+    let mut banner = [0; 11];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut banner))
+        .await
+        .expect("--no-rewrite 下代理仍在等待客户端的首个数据")
+        .unwrap();
+    assert_eq!(&banner, b"220 ready\r\n");
+}
+
+#[tokio::test]
+async fn requests_are_rewritten_by_default() {
+    let proxy = Proxy::spawn(&["-f", "Replaced/1.0"]).await;
+    let received = forward(&proxy, REQUEST).await;
+    assert_eq!(user_agent(&received).as_deref(), Some("Replaced/1.0"));
+}
+
+#[tokio::test]
+async fn rewrite_is_logged_as_one_structured_event() {
+    let proxy = Proxy::spawn(&["-f", "Replaced/1.0", "-l", "debug", "--log-format", "json"]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    let client = stream.local_addr().unwrap().to_string();
+    stream.write_all(REQUEST).await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await;
+
+    proxy.wait_for_log("User-Agent 已修改", Duration::from_secs(5)).await;
+    let events = json_events(&proxy, "User-Agent 已修改");
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["fields"]["method"], "GET");
+    assert_eq!(event["fields"]["path"], "/index.html");
+    assert_eq!(event["fields"]["old_ua"], "curl/8.4.0");
+    assert_eq!(event["fields"]["new_ua"], "Replaced/1.0");
+    // target 与 client 来自外层的连接 span
+    let conn = &event["spans"][0];
+    assert_eq!(conn["name"], "conn");
+    assert_eq!(conn["target"], target.addr.to_string());
+    assert_eq!(conn["client"], client);
+}
+
+#[tokio::test]
+async fn rewrite_delta_matches_the_user_agent_length_change() {
+    let new_ua = "Replaced/1.0 (with a longer value)";
+    let proxy = Proxy::spawn(&["-f", new_ua, "-l", "debug", "--log-format", "json"]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(REQUEST).await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await;
+    stream.shutdown().await.unwrap();
+    read_until_closed(&mut stream, Duration::from_secs(5)).await.unwrap();
+
+    assert!(proxy.wait_for_log("连接结束，改写统计", Duration::from_secs(5)).await);
+    let events = json_events(&proxy, "连接结束，改写统计");
+    let fields = &events[0]["fields"];
+    let delta = new_ua.len() as i64 - "curl/8.4.0".len() as i64;
+    assert_eq!(fields["rewrite_delta"], delta);
+    assert_eq!(fields["header_bytes"], REQUEST.len() as i64 + delta);
+    assert_eq!(fields["forwarded_up"], REQUEST.len() as i64 + delta);
+}
+
+#[tokio::test]
+async fn connect_requests_bypass_the_rewrite() {
+    let proxy = Proxy::spawn(&["-f", "Replaced/1.0", "-l", "debug"]).await;
+    let target = Target::sink().await;
+    let request: &[u8] = b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\nUser-Agent: curl/8.4.0\r\n\r\n";
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(request).await.unwrap();
+    // 隧道建立后的 TLS 数据同样原样转发
+    stream.write_all(b"\x16\x03\x01\x00\x05hello").await.unwrap();
+    let received = target.wait_received(0, request.len() + 10, Duration::from_secs(5)).await;
+    assert_eq!(&received[..request.len()], request);
+    assert_eq!(&received[request.len()..], b"\x16\x03\x01\x00\x05hello");
+
+    assert!(proxy.wait_for_log("检测到 CONNECT 隧道请求，跳过 User-Agent 修改", Duration::from_secs(5)).await);
+    assert_eq!(proxy.log_count("User-Agent 已修改"), 0);
+    assert_eq!(proxy.log_count("检测到 HTTP 请求"), 0);
+}
+
+#[tokio::test]
+async fn rewritten_request_carries_no_trailing_padding() {
+    for new_ua in ["UA", "Replaced/1.0 (a much longer value than the original)"] {
+        let proxy = Proxy::spawn(&["-f", new_ua]).await;
+        let target = Target::sink().await;
+        let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+        assert_eq!(reply, 0);
+        // 请求头分两段到达
+        let (first, rest) = REQUEST.split_at(20);
+        stream.write_all(first).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(rest).await.unwrap();
+        target.wait_heads(0, 1, Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let expected = String::from_utf8_lossy(REQUEST).replace("curl/8.4.0", new_ua);
+        let received = target.received(0);
+        assert!(!received.contains(&0), "目标收到了填充的 NUL 字节");
+        assert_eq!(received, expected.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn pipelined_requests_without_a_user_agent_are_not_logged_as_errors() {
+    let proxy = Proxy::spawn(&["--rewrite-all-requests", "-l", "debug"]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    let request = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    for _ in 0..3 {
+        stream.write_all(request).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    target.wait_heads(0, 3, Duration::from_secs(5)).await;
+
+    // 只有第一个请求以 error 级别记录，后续请求降为 debug
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while proxy.log_count("未找到 User-Agent 头") < 3 {
+        assert!(tokio::time::Instant::now() < deadline, "{:?}", proxy.logs());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let missing: Vec<String> = proxy.logs().into_iter().filter(|line| line.contains("未找到 User-Agent 头")).collect();
+    assert_eq!(missing.iter().filter(|line| line.contains("ERROR")).count(), 1, "{missing:?}");
+    assert_eq!(missing.iter().filter(|line| line.contains("DEBUG")).count(), 2, "{missing:?}");
+}
@@ -0,0 +1,179 @@
+//! 信号处理（SIGUSR2 状态摘要、SIGHUP 重新加载、SIGTERM 退出）的集成测试
+#![cfg(unix)]
+mod common;
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use common::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn send_signal(proxy: &Proxy, signal: libc::c_int) {
+    assert_eq!(unsafe { libc::kill(proxy.pid() as libc::pid_t, signal) }, 0);
+}
+
+#[tokio::test]
+async fn sigusr2_logs_a_status_summary() {
+    let proxy = Proxy::spawn(&["--log-format", "json"]).await;
+    let target = Target::sink().await;
+    let (reply, stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+
+    send_signal(&proxy, libc::SIGUSR2);
+    assert!(proxy.wait_for_log("运行状态摘要", Duration::from_secs(5)).await);
+    let fields = &json_events(&proxy, "运行状态摘要")[0]["fields"];
+    assert_eq!(fields["active_connections"], 1);
+    assert_eq!(fields["total_connections"], 1);
+    assert_eq!(fields["config_hash"].as_str().unwrap().len(), 16);
+    drop(stream);
+
+    // 每次收到信号都输出一次，且进程继续运行
+    send_signal(&proxy, libc::SIGUSR2);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while proxy.log_count("运行状态摘要") < 2 {
+        assert!(tokio::time::Instant::now() < deadline, "第二次 SIGUSR2 未输出摘要");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let (reply, _stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+}
+
+/// 经代理发送一个请求，返回目标收到的 User-Agent
+async fn forwarded_user_agent(proxy: &Proxy) -> Option<String> {
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    user_agent(&target.wait_heads(0, 1, Duration::from_secs(5)).await)
+}
+
+fn user_agent_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ua4f-ua-{}-{}.txt", name, std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn user_agent_file_is_reloaded_on_sighup() {
+    let path = user_agent_file("reload", "FromFile/1.0\n");
+    let proxy = Proxy::spawn(&["--user-agent-file", path.to_str().unwrap()]).await;
+    assert_eq!(forwarded_user_agent(&proxy).await.as_deref(), Some("FromFile/1.0"));
+
+    std::fs::write(&path, "FromFile/2.0\n").unwrap();
+    send_signal(&proxy, libc::SIGHUP);
+    assert!(proxy.wait_for_log("已重新加载 User-Agent: FromFile/2.0", Duration::from_secs(5)).await);
+    assert_eq!(forwarded_user_agent(&proxy).await.as_deref(), Some("FromFile/2.0"));
+
+    // 含换行的新值被拒绝，保留当前值
+    std::fs::write(&path, "FromFile/3.0\r\nX-Injected: 1\n").unwrap();
+    send_signal(&proxy, libc::SIGHUP);
+    assert!(proxy.wait_for_log("重新加载 User-Agent 失败，保留当前值", Duration::from_secs(5)).await);
+    assert_eq!(forwarded_user_agent(&proxy).await.as_deref(), Some("FromFile/2.0"));
+    let _ = std::fs::remove_file(path);
+}
+
+/// 以指定的 --reload-failure-mode 启动，保持一个转发中的连接，然后写入无效的 User-Agent 并发送 SIGHUP
+async fn bad_reload(mode: &str) -> (Proxy, Target, tokio::net::TcpStream, PathBuf) {
+    let path = user_agent_file(mode, "FromFile/1.0\n");
+    let proxy = Proxy::spawn(&["--user-agent-file", path.to_str().unwrap(), "--reload-failure-mode", mode]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"\x00\x01before").await.unwrap();
+    target.wait_received(0, 8, Duration::from_secs(5)).await;
+
+    std::fs::write(&path, "").unwrap();
+    send_signal(&proxy, libc::SIGHUP);
+    (proxy, target, stream, path)
+}
+
+#[tokio::test]
+async fn bad_reload_keeps_the_current_value_in_keep_mode() {
+    let (proxy, target, mut stream, path) = bad_reload("keep").await;
+    assert!(proxy.wait_for_log("重新加载 User-Agent 失败，保留当前值", Duration::from_secs(5)).await);
+    stream.write_all(b"after").await.unwrap();
+    target.wait_received(0, 13, Duration::from_secs(5)).await;
+    // 仍接受新连接，并继续使用旧值
+    assert_eq!(forwarded_user_agent(&proxy).await.as_deref(), Some("FromFile/1.0"));
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn bad_reload_stops_accepting_in_fail_closed_mode() {
+    let (mut proxy, target, mut stream, path) = bad_reload("fail-closed").await;
+    assert!(proxy.wait_for_log("已停止接受新连接", Duration::from_secs(5)).await);
+    assert!(proxy.log_count("ERROR") > 0);
+    // 已建立的连接继续转发，新连接被拒绝
+    stream.write_all(b"after").await.unwrap();
+    target.wait_received(0, 13, Duration::from_secs(5)).await;
+    assert!(tokio::net::TcpStream::connect(proxy.addr).await.is_err());
+    // 进程仍在运行，收到退出信号后正常退出
+    assert!(proxy.wait_exit(Duration::from_millis(200)).await.is_none());
+    drop(stream);
+    send_signal(&proxy, libc::SIGTERM);
+    assert!(proxy.wait_exit(Duration::from_secs(5)).await.is_some());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn user_agent_file_with_crlf_is_rejected_at_startup() {
+    let path = user_agent_file("crlf", "FromFile/1.0\r\nX-Injected: 1\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_ua4f"))
+        .args(["-b", "127.0.0.1", "-p", &free_port().to_string(), "--no-file-log"])
+        .args(["--user-agent-file", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(path);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid --user-agent-file"));
+}
+
+/// 在一个转发中的连接存活时发送 SIGTERM，返回退出等待超时后客户端读到的结果
+async fn read_after_forced_close(close_mode: &str) -> std::io::Result<usize> {
+    let mut proxy = Proxy::spawn(&["--shutdown-timeout", "1", "--shutdown-close-mode", close_mode]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"\x00\x01keep relaying").await.unwrap();
+    target.wait_received(0, 15, Duration::from_secs(5)).await;
+
+    send_signal(&proxy, libc::SIGTERM);
+    let mut buf = [0; 64];
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf)).await.expect("退出时连接未被关闭");
+    assert!(proxy.wait_exit(Duration::from_secs(5)).await.is_some());
+    assert!(proxy.log_count("强制关闭") > 0);
+    read
+}
+
+#[tokio::test]
+async fn leftover_connections_get_a_fin_at_shutdown_by_default() {
+    assert_eq!(read_after_forced_close("fin").await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn leftover_connections_get_a_rst_with_rst_close_mode() {
+    let err = read_after_forced_close("rst").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+}
+
+#[tokio::test]
+async fn listeners_are_released_before_draining_connections() {
+    let metrics = std::net::SocketAddr::from(([127, 0, 0, 1], free_port()));
+    let mut proxy = Proxy::spawn(&["--metrics-addr", &metrics.to_string(), "--shutdown-timeout", "10"]).await;
+    let target = Target::sink().await;
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"\x00\x01keep relaying").await.unwrap();
+    target.wait_received(0, 15, Duration::from_secs(5)).await;
+
+    send_signal(&proxy, libc::SIGTERM);
+    assert!(proxy.wait_for_log("收到退出信号，停止接受新连接", Duration::from_secs(5)).await);
+    // 仍在等待连接结束时，SOCKS 与指标端口都已可以重新绑定
+    std::net::TcpListener::bind(proxy.addr).expect("SOCKS 端口未释放");
+    std::net::TcpListener::bind(metrics).expect("指标端口未释放");
+    assert!(proxy.wait_exit(Duration::from_millis(200)).await.is_none());
+
+    drop(stream);
+    assert!(proxy.wait_exit(Duration::from_secs(5)).await.is_some());
+}
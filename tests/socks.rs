@@ -0,0 +1,71 @@
+//! SOCKS5 协议层面的集成测试：应答内容与异常握手的处理
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn reply_carries_the_advertised_address() {
+    let proxy = Proxy::spawn(&["--advertise-addr", "203.0.113.7:4000"]).await;
+    let target = Target::sink().await;
+
+    let (reply, _stream, bound) = socks5_connect_bound(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    assert_eq!(bound, "203.0.113.7:4000".parse::<SocketAddr>().unwrap());
+}
+
+#[tokio::test]
+async fn garbage_handshake_is_logged_and_closed_without_affecting_others() {
+    let proxy = Proxy::spawn(&[]).await;
+    let target = Target::sink().await;
+    let (reply, mut established) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+
+    let mut garbage = TcpStream::connect(proxy.addr).await.unwrap();
+    let peer = garbage.local_addr().unwrap();
+    garbage.write_all(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").await.unwrap();
+    assert!(read_until_closed(&mut garbage, Duration::from_secs(5)).await.is_some(), "无效握手的连接未被关闭");
+    assert!(proxy.wait_for_log("SOCKS5 握手无效", Duration::from_secs(5)).await);
+    let logged = proxy.logs().into_iter().find(|line| line.contains("SOCKS5 握手无效")).unwrap();
+    assert!(logged.contains(&format!("client={}", peer)), "{logged}");
+
+    // 已建立的连接照常转发，新连接也照常握手
+    established.write_all(b"still here").await.unwrap();
+    target.wait_received(0, 10, Duration::from_secs(5)).await;
+    let (reply, _stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+}
+
+/// 接受队列已满的监听地址：内核丢弃新的 SYN，连接它会一直停在重传中。返回监听器与占满队列的连接
+async fn stalled_listener() -> (tokio::net::TcpListener, Vec<TcpStream>) {
+    let socket = tokio::net::TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut queued = Vec::new();
+    while let Ok(Ok(stream)) = tokio::time::timeout(Duration::from_millis(200), TcpStream::connect(addr)).await {
+        queued.push(stream);
+    }
+    (listener, queued)
+}
+
+#[tokio::test]
+async fn connect_timeout_and_refusal_get_distinct_replies() {
+    let proxy = Proxy::spawn(&["--connect-timeout", "1"]).await;
+
+    let (stalled, _queued) = stalled_listener().await;
+    let started = std::time::Instant::now();
+    let (reply, _stream) = socks5_connect(proxy.addr, stalled.local_addr().unwrap()).await;
+    assert_eq!(reply, 0x06, "连接超时应回复 TTL expired");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(proxy.wait_for_log("与目标的连接超时", Duration::from_secs(5)).await);
+
+    // 绑定后立即关闭，得到一个没有监听者的端口
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let (reply, _stream) = socks5_connect(proxy.addr, closed).await;
+    assert_eq!(reply, 0x05, "连接被拒绝应回复 Connection refused");
+}
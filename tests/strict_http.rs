@@ -0,0 +1,129 @@
+//! 声明为 HTTP 的端口、--strict-http 与异常请求处理的集成测试
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use tokio::io::AsyncWriteExt;
+
+/// TLS ClientHello 记录的开头
+const CLIENT_HELLO: &[u8] = b"\x16\x03\x01\x00\xa5\x01\x00\x00\xa1\x03\x03";
+
+#[tokio::test]
+async fn tls_on_an_http_port_is_logged_and_forwarded() {
+    let target = Target::sink().await;
+    let port = target.addr.port().to_string();
+    let proxy = Proxy::spawn(&["--http-ports", &port]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(CLIENT_HELLO).await.unwrap();
+    assert_eq!(target.wait_received(0, CLIENT_HELLO.len(), Duration::from_secs(5)).await, CLIENT_HELLO);
+    assert!(proxy.wait_for_log(&format!("HTTP 端口 {port} 上收到TLS流量"), Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn tls_on_an_http_port_is_closed_under_strict_http() {
+    let target = Target::sink().await;
+    let port = target.addr.port().to_string();
+    let proxy = Proxy::spawn(&["--http-ports", &port, "--strict-http"]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(CLIENT_HELLO).await.unwrap();
+    assert_eq!(read_until_closed(&mut stream, Duration::from_secs(5)).await, Some(Vec::new()));
+    assert!(proxy.wait_for_log(&format!("HTTP 端口 {port} 上收到TLS流量"), Duration::from_secs(5)).await);
+    // 目标连接已建立但未收到任何数据
+    assert_eq!(target.wait_received(0, 0, Duration::from_secs(5)).await, b"");
+}
+
+/// 以方法名开头、却没有 ` HTTP/x.y` 版本的请求行
+const INVALID_REQUEST_LINE: &[u8] = b"GET /index.html\r\nUser-Agent: curl/8.4.0\r\n\r\n";
+
+#[tokio::test]
+async fn valid_request_line_is_rewritten_under_strict_http() {
+    let target = Target::sink().await;
+    let proxy = Proxy::spawn(&["--strict-http", "-f", "Replaced/1.0"]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(b"GET /index.html HTTP/1.1\r\nUser-Agent: curl/8.4.0\r\n\r\n").await.unwrap();
+    let received = target.wait_heads(0, 1, Duration::from_secs(5)).await;
+    assert_eq!(user_agent(&received).as_deref(), Some("Replaced/1.0"));
+    assert_eq!(proxy.log_count("请求行无效"), 0);
+}
+
+#[tokio::test]
+async fn invalid_request_line_is_forwarded_unmodified() {
+    let target = Target::sink().await;
+    let proxy = Proxy::spawn(&["-f", "Replaced/1.0"]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(INVALID_REQUEST_LINE).await.unwrap();
+    let received = target.wait_received(0, INVALID_REQUEST_LINE.len(), Duration::from_secs(5)).await;
+    assert_eq!(received, INVALID_REQUEST_LINE);
+    assert!(proxy.wait_for_log("请求行无效", Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn invalid_request_line_is_closed_under_strict_http() {
+    let target = Target::sink().await;
+    let proxy = Proxy::spawn(&["--strict-http"]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(INVALID_REQUEST_LINE).await.unwrap();
+    assert_eq!(read_until_closed(&mut stream, Duration::from_secs(5)).await, Some(Vec::new()));
+    assert!(proxy.wait_for_log("请求行无效", Duration::from_secs(5)).await);
+    assert_eq!(target.wait_received(0, 0, Duration::from_secs(5)).await, b"");
+}
+
+#[tokio::test]
+async fn http_port_ranges_match_targets_inside_the_range() {
+    for inside in [true, false] {
+        let target = Target::sink().await;
+        let port = target.addr.port();
+        let ranges = if inside {
+            format!("{}-{}", port - 1, port + 1)
+        } else {
+            format!("{}-{}", port + 1, port.saturating_add(100))
+        };
+        let proxy = Proxy::spawn(&["--http-ports", &ranges]).await;
+
+        let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+        assert_eq!(reply, 0);
+        stream.write_all(CLIENT_HELLO).await.unwrap();
+        target.wait_received(0, CLIENT_HELLO.len(), Duration::from_secs(5)).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(proxy.log_count(&format!("HTTP 端口 {port} 上收到TLS流量")) > 0, inside, "{ranges}");
+    }
+}
+
+/// User-Agent 值中嵌有单独的 `\n`，宽松的下游解析器会把其后内容当作额外的头部
+const UA_INJECTION: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\nUser-Agent: curl/8.4.0\nX-Injected: 1\r\n\r\n";
+
+#[tokio::test]
+async fn user_agent_injection_is_logged() {
+    let target = Target::sink().await;
+    let proxy = Proxy::spawn(&[]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(UA_INJECTION).await.unwrap();
+    target.wait_heads(0, 1, Duration::from_secs(5)).await;
+    assert!(proxy.wait_for_log("User-Agent 含有控制字符，疑似头部注入", Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn user_agent_injection_is_closed_with_reject_ua_injection() {
+    let target = Target::sink().await;
+    let proxy = Proxy::spawn(&["--reject-ua-injection"]).await;
+
+    let (reply, mut stream) = socks5_connect(proxy.addr, target.addr).await;
+    assert_eq!(reply, 0);
+    stream.write_all(UA_INJECTION).await.unwrap();
+    assert_eq!(read_until_closed(&mut stream, Duration::from_secs(5)).await, Some(Vec::new()));
+    assert!(proxy.wait_for_log("User-Agent 含有控制字符，疑似头部注入", Duration::from_secs(5)).await);
+    assert_eq!(target.wait_received(0, 0, Duration::from_secs(5)).await, b"");
+}
@@ -0,0 +1,44 @@
+//! systemd（Type=notify）就绪、看门狗与停止通知的集成测试，需启用 systemd 特性
+#![cfg(all(target_os = "linux", feature = "systemd"))]
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use tokio::net::{TcpStream, UnixDatagram};
+
+/// 在 `timeout` 内收到的下一条通知
+async fn next_notification(socket: &UnixDatagram, timeout: Duration) -> String {
+    let mut buf = [0; 256];
+    let n = tokio::time::timeout(timeout, socket.recv(&mut buf)).await.expect("未收到 systemd 通知").unwrap();
+    String::from_utf8_lossy(&buf[..n]).into_owned()
+}
+
+#[tokio::test]
+async fn ready_is_sent_after_bind_followed_by_watchdog_and_stopping() {
+    let path = std::env::temp_dir().join(format!("ua4f-notify-it-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+
+    let mut proxy = Proxy::spawn_with(&[], |command| {
+        command.env("NOTIFY_SOCKET", &path).env("WATCHDOG_USEC", "200000").env_remove("WATCHDOG_PID");
+    })
+    .await;
+    assert_eq!(next_notification(&socket, Duration::from_secs(5)).await, "READY=1");
+    // 收到 READY 时监听已在接受连接
+    TcpStream::connect(proxy.addr).await.unwrap();
+    assert_eq!(next_notification(&socket, Duration::from_secs(2)).await, "WATCHDOG=1");
+
+    assert_eq!(unsafe { libc::kill(proxy.pid() as libc::pid_t, libc::SIGTERM) }, 0);
+    loop {
+        match next_notification(&socket, Duration::from_secs(5)).await.as_str() {
+            "WATCHDOG=1" => continue,
+            state => {
+                assert_eq!(state, "STOPPING=1");
+                break;
+            }
+        }
+    }
+    assert!(proxy.wait_exit(Duration::from_secs(10)).await.is_some());
+    let _ = std::fs::remove_file(path);
+}